@@ -2,7 +2,9 @@ use bevy::{
     log::{Level, LogPlugin},
     prelude::*,
 };
-use bevy_asset_packer::{asset_bundling_options::AssetBundlingOptions, bundled_asset_plugin::BundledAssetIoPlugin};
+use bevy_asset_packer::{
+    asset_bundling_options::AssetBundlingOptions, bundled_asset_plugin::BundledAssetIoPlugin, compression::Compression,
+};
 
 #[derive(Component)]
 enum Direction {
@@ -13,7 +15,7 @@ enum Direction {
 fn main() {
     let mut options = AssetBundlingOptions::default();
     options.encode_file_names = true;
-    options.compress_on = true;
+    options.compression = Compression::Deflate(9);
     options.set_encryption_key([57, 206, 200, 7, 215, 17, 45, 219, 131, 171, 8, 214, 85, 12, 129, 176]);
 
     App::new()
@@ -1,23 +1,66 @@
 use std::{
     env, fs,
-    io::Read,
+    io::Write,
     path::{Path, PathBuf},
+    time::UNIX_EPOCH,
 };
 
-use miniz_oxide::deflate::compress_to_vec;
+use globset::{Glob, GlobSet, GlobSetBuilder};
+use ignore::WalkBuilder;
+use sha2::{Digest, Sha256};
 
-use crate::asset_bundling_options::AssetBundlingOptions;
+use crate::{
+    asset_bundling_options::AssetBundlingOptions,
+    build_cache::{BuildCache, BuildCacheWriter, SourceStamp},
+    bundle_header::{BundleHeader, FORMAT_VERSION, KEY_SALT_LEN},
+    bundle_signature::{ManifestWriter, MANIFEST_ENTRY_NAME},
+    compression::{Compression, CompressionPolicy, CompressionRule},
+    crypt::{derive_nonce, random_nonce},
+    mounted_bundle::normalize_path,
+};
 
 pub struct AssetBundler {
     pub options: AssetBundlingOptions,
     pub asset_folder: String,
+    /// Path of the produced bundle file, defaults to `asset_bundle_name` next to the built executable.
+    pub output_path: Option<PathBuf>,
+    /// Compression overrides for matching files, the first matching rule wins over `options.compression`.
+    pub compression_rules: Vec<CompressionRule>,
+    /// Minimum fraction of the size compression has to save, entries saving less are stored uncompressed.
+    pub min_compression_saving: Option<f32>,
+    /// File keeping encoded entries between builds, so only changed assets are encoded again.
+    pub cache_path: Option<PathBuf>,
+    /// Print `cargo:rerun-if-changed` for the asset folder and every packed file, and `cargo:rerun-if-env-changed` for
+    /// the variables the key is read from. On by default in build scripts.
+    pub emit_rerun_if_changed: bool,
+    /// Globs of files to pack, every file is packed when empty.
+    pub include_patterns: Vec<String>,
+    /// Globs of files to leave out, applied after `include_patterns`.
+    pub exclude_patterns: Vec<String>,
+    /// Ed25519 key signing the hashes of every entry, checked at runtime against
+    /// [`AssetBundlingOptions::verifying_key`].
+    pub signing_key: Option<[u8; 32]>,
+    /// Salt for a key derived from the passphrase, stored in the bundle header. A random salt is used when unset.
+    pub key_salt: Option<[u8; KEY_SALT_LEN]>,
 }
 
+/// Name of the files listing assets to leave out of the bundle, using gitignore syntax.
+pub const ASSET_IGNORE_FILE_NAME: &str = ".assetignore";
+
 impl Default for AssetBundler {
     fn default() -> Self {
         Self {
             options: AssetBundlingOptions::default(),
             asset_folder: "assets".to_owned(),
+            output_path: None,
+            compression_rules: Vec::new(),
+            min_compression_saving: None,
+            cache_path: None,
+            emit_rerun_if_changed: env::var_os("OUT_DIR").is_some(),
+            include_patterns: Vec::new(),
+            exclude_patterns: Vec::new(),
+            signing_key: None,
+            key_salt: None,
         }
     }
 }
@@ -26,7 +69,7 @@ impl From<AssetBundlingOptions> for AssetBundler {
     fn from(options: AssetBundlingOptions) -> Self {
         Self {
             options,
-            asset_folder: "assets".to_owned(),
+            ..Self::default()
         }
     }
 }
@@ -37,26 +80,172 @@ impl AssetBundler {
         self
     }
 
+    pub fn with_output_path(&mut self, path: impl Into<PathBuf>) -> &mut Self {
+        self.output_path = Some(path.into());
+        self
+    }
+
+    /// Uses `compression` for files whose path inside the asset folder matches the glob `pattern`, for example
+    /// `Compression::Stored` for `*.png`. Rules are tried in the order they were added.
+    pub fn with_compression_rule(&mut self, pattern: impl Into<String>, compression: Compression) -> &mut Self {
+        self.compression_rules.push(CompressionRule {
+            pattern: pattern.into(),
+            compression,
+        });
+        self
+    }
+
+    /// Stores entries uncompressed when compression saves less than `min_saving` of their size, e.g. `0.05` for 5%.
+    pub fn with_min_compression_saving(&mut self, min_saving: f32) -> &mut Self {
+        self.min_compression_saving = Some(min_saving);
+        self
+    }
+
+    /// Only packs files matching `pattern`, see [`Self::with_exclude`] for the glob syntax. Can be called several times.
+    pub fn with_include(&mut self, pattern: impl Into<String>) -> &mut Self {
+        self.include_patterns.push(pattern.into());
+        self
+    }
+
+    /// Leaves out files matching `pattern`. As in gitignore, a pattern without `/` like `*.psd` or `.DS_Store` is
+    /// matched against file names, others like `raw/**` against the path inside the asset folder.
+    ///
+    /// Files can also be left out with [`ASSET_IGNORE_FILE_NAME`] files in the asset folder or any folder below it.
+    pub fn with_exclude(&mut self, pattern: impl Into<String>) -> &mut Self {
+        self.exclude_patterns.push(pattern.into());
+        self
+    }
+
+    /// Keeps encoded entries in the file at `path` and reuses them for assets that did not change since the previous
+    /// build. The cache is discarded when the bundling options change.
+    pub fn with_cache_path(&mut self, path: impl Into<PathBuf>) -> &mut Self {
+        self.cache_path = Some(path.into());
+        self
+    }
+
+    /// Enables or disables printing `cargo:rerun-if-changed` lines, so cargo reruns build.rs when assets change.
+    pub fn with_rerun_if_changed(&mut self, enabled: bool) -> &mut Self {
+        self.emit_rerun_if_changed = enabled;
+        self
+    }
+
+    /// Signs the bundle with the Ed25519 `signing_key`, see [`crate::bundle_signature`] to generate one and get its
+    /// public key for [`AssetBundlingOptions::set_verifying_key`].
+    pub fn with_signing_key(&mut self, signing_key: [u8; 32]) -> &mut Self {
+        self.signing_key = Some(signing_key);
+        self
+    }
+
+    /// Derives the key from the passphrase with `salt` instead of a random salt, so bundles encrypted with a passphrase
+    /// can be reproduced together with [`AssetBundlingOptions::deterministic_nonces`]. Use a different salt for every
+    /// passphrase.
+    pub fn with_key_salt(&mut self, salt: [u8; KEY_SALT_LEN]) -> &mut Self {
+        self.key_salt = Some(salt);
+        self
+    }
+
+    /// Writes the bundle to cargo's `OUT_DIR`, so it can be embedded with
+    /// [`include_asset_bundle`](crate::include_asset_bundle). Only works from a build script.
+    pub fn with_out_dir_output(&mut self) -> anyhow::Result<&mut Self> {
+        let out_dir = env::var_os("OUT_DIR")
+            .ok_or_else(|| anyhow::Error::msg("OUT_DIR is not set, embedding requires running from build.rs."))?;
+        Ok(self.with_output_path(PathBuf::from(out_dir).join(&self.options.asset_bundle_name)))
+    }
+
     pub fn build(&self) -> anyhow::Result<()> {
-        if self.options.encryption_on && self.options.encryption_key.is_none() {
+        if self.options.encryption_on && !self.options.is_encryption_ready() {
             return Err(anyhow::Error::msg(
                 "Asset encryption is enabled but encryption key is not provided.",
             ));
         }
 
+        let policy = CompressionPolicy::new(
+            self.options.compression,
+            &self.compression_rules,
+            self.min_compression_saving,
+        )?;
         let asset_dir = PathBuf::from(&self.asset_folder);
         if asset_dir.is_dir() {
-            let exe_dir = get_exe_dir()?;
-            let bundle_file_path = exe_dir.join(self.options.asset_bundle_name.clone());
+            let bundle_file_path = match &self.output_path {
+                Some(path) => path.clone(),
+                None => get_exe_dir()?.join(&self.options.asset_bundle_name),
+            };
             if let Some(bundle_file_dir) = bundle_file_path.parent() {
                 if !bundle_file_dir.exists() {
                     fs::create_dir_all(bundle_file_dir)?;
                 }
             }
 
-            let tar_file = fs::File::create(bundle_file_path)?;
+            let mut files = Vec::new();
+            let filter = FileFilter::new(&self.include_patterns, &self.exclude_patterns)?;
+            collect_files(&asset_dir, &filter, &mut files)?;
+            files.sort();
+            if self.emit_rerun_if_changed {
+                println!("cargo:rerun-if-changed={}", asset_dir.display());
+                for file in &files {
+                    println!("cargo:rerun-if-changed={}", file.display());
+                }
+                for var in self.options.key_env_vars() {
+                    println!("cargo:rerun-if-env-changed={}", var);
+                }
+            }
+
+            let fingerprint = self.cache_fingerprint();
+            let mut cache = self
+                .cache_path
+                .as_ref()
+                .map(|path| BuildCache::load(path, &fingerprint));
+            let key_salt = self.key_salt(cache.as_ref())?;
+            let options = self.options.resolve_key(self.options.key_cipher, key_salt.as_ref())?;
+            let key_check = match &options.encryption_key {
+                Some(key) => derive_nonce(key.as_bytes(), b"build cache", &[]),
+                None => [0; 32],
+            };
+            if let Some(cache) = &mut cache {
+                cache.retain_if_key_matches(&key_check);
+            }
+            let mut cache_writer = match &self.cache_path {
+                Some(path) => Some(BuildCacheWriter::create(
+                    path,
+                    &fingerprint,
+                    key_salt.as_ref(),
+                    &key_check,
+                )?),
+                None => None,
+            };
+
+            let mut header = BundleHeader::from_options(&options);
+            header.key_salt = key_salt;
+            header.signed = self.signing_key.is_some();
+            let header_bytes = header.to_bytes()?;
+            let mut tar_file = fs::File::create(bundle_file_path)?;
+            tar_file.write_all(&header_bytes)?;
             let mut tar_builder = tar::Builder::new(tar_file);
-            archive_dir(&mut tar_builder, &asset_dir, &self.options)?;
+            let context = EncodeContext {
+                prefix: &asset_dir,
+                options: &options,
+                policy: &policy,
+                cache: cache.as_ref(),
+            };
+            let mut manifest = self.signing_key.map(|_| ManifestWriter::default());
+            archive_files(
+                &mut tar_builder,
+                &files,
+                &context,
+                cache_writer.as_mut(),
+                manifest.as_mut(),
+            )?;
+            if let (Some(signing_key), Some(manifest)) = (&self.signing_key, manifest) {
+                append_entry(
+                    &mut tar_builder,
+                    Path::new(MANIFEST_ENTRY_NAME),
+                    &manifest.sign(signing_key, &header_bytes),
+                )?;
+            }
+            tar_builder.into_inner()?;
+            if let Some(cache_writer) = cache_writer {
+                cache_writer.finish()?;
+            }
             Ok(())
         } else {
             Err(anyhow::Error::msg(format!(
@@ -66,81 +255,269 @@ impl AssetBundler {
             )))
         }
     }
+
+    /// Salt for a key derived from the passphrase. Without [`Self::key_salt`] the salt of the build cache is kept, so
+    /// its entries stay valid.
+    fn key_salt(&self, cache: Option<&BuildCache>) -> anyhow::Result<Option<[u8; KEY_SALT_LEN]>> {
+        if self.options.encryption_passphrase.is_none() || !self.options.encryption_on {
+            return Ok(None);
+        }
+        if let Some(salt) = self.key_salt {
+            return Ok(Some(salt));
+        }
+        match cache.and_then(BuildCache::key_salt) {
+            Some(salt) => Ok(Some(*salt)),
+            None => Ok(Some(random_nonce()?)),
+        }
+    }
+
+    /// Hash of every setting that changes how entries are encoded. Keys are left out, the cache checks them with a
+    /// value derived from the key instead.
+    fn cache_fingerprint(&self) -> [u8; 32] {
+        let options = &self.options;
+        let settings = format!(
+            "{} {} {} {} {} {:?} {:?} {:?} {} {:?} {:?}",
+            FORMAT_VERSION,
+            options.encode_file_names,
+            options.encryption_on,
+            options.encryption_passphrase.is_some(),
+            options.key_provider.is_some(),
+            options.cipher(),
+            options.cipher_mode,
+            options.compression,
+            options.deterministic_nonces,
+            self.compression_rules,
+            self.min_compression_saving,
+        );
+        Sha256::digest(settings).into()
+    }
 }
 
-fn archive_dir(
-    builder: &mut tar::Builder<fs::File>,
-    asset_dir: &Path,
-    options: &AssetBundlingOptions,
-) -> anyhow::Result<()> {
-    archive_dir_recursive(builder, asset_dir, asset_dir, options)?;
-    Ok(())
+/// Entry ready to be appended to the archive.
+struct EncodedEntry {
+    relative_path: PathBuf,
+    stamp: SourceStamp,
+    name_in_archive: PathBuf,
+    data: Vec<u8>,
+}
+
+/// Everything needed to encode one file of the asset folder.
+struct EncodeContext<'a> {
+    prefix: &'a Path,
+    options: &'a AssetBundlingOptions,
+    policy: &'a CompressionPolicy,
+    cache: Option<&'a BuildCache>,
 }
 
-fn archive_dir_recursive(
+fn archive_files(
     builder: &mut tar::Builder<fs::File>,
-    dir: &Path,
-    prefix: &Path,
-    options: &AssetBundlingOptions,
+    files: &[PathBuf],
+    context: &EncodeContext,
+    mut cache_writer: Option<&mut BuildCacheWriter>,
+    mut manifest: Option<&mut ManifestWriter>,
 ) -> anyhow::Result<()> {
-    for entry_result in fs::read_dir(dir)? {
-        let entry_path = entry_result?.path();
-        if entry_path.is_dir() {
-            archive_dir_recursive(builder, &entry_path, prefix, options)?;
-        } else {
-            let mut name_in_archive = entry_path.strip_prefix(prefix)?.to_owned();
-            if options.encode_file_names {
-                name_in_archive = options.try_encode_path(&name_in_archive)?;
-            }
-            let mut file = fs::File::open(entry_path.clone())?;
-
-            if options.compress_on {
-                let mut plain = Vec::new();
-                file.read_to_end(&mut plain)?;
-
-                let compressed = compress_to_vec(&plain, 9);
-                if options.is_encryption_ready() {
-                    if let Some(encrypted) = options.try_encrypt(&compressed)? {
-                        let mut header = tar::Header::new_gnu();
-                        header.set_path(name_in_archive)?;
-                        let metadata = fs::metadata(&entry_path)?;
-                        header.set_metadata(&metadata);
-                        header.set_size(encrypted.len() as u64);
-                        header.set_cksum();
-                        builder.append(&header, encrypted.as_slice())?;
-                        continue;
-                    }
-                } else {
-                    let mut header = tar::Header::new_gnu();
-                    header.set_path(name_in_archive)?;
-                    let metadata = fs::metadata(&entry_path)?;
-                    header.set_metadata(&metadata);
-                    header.set_size(compressed.len() as u64);
-                    header.set_cksum();
-                    builder.append(&header, compressed.as_slice())?;
-                    continue;
-                }
-            } else if options.is_encryption_ready() {
-                let mut plain = Vec::new();
-                file.read_to_end(&mut plain)?;
-
-                if let Some(encrypted) = options.try_encrypt(&plain)? {
-                    let mut header = tar::Header::new_gnu();
-                    header.set_path(name_in_archive)?;
-                    let metadata = fs::metadata(&entry_path)?;
-                    header.set_metadata(&metadata);
-                    header.set_size(encrypted.len() as u64);
-                    header.set_cksum();
-                    builder.append(&header, encrypted.as_slice())?;
-                    continue;
+    // Files are encoded in batches so memory stays bounded, and appended in sorted order whatever thread
+    // finished first.
+    for batch in files.chunks(ENCODE_BATCH_SIZE) {
+        for entry in encode_batch(batch, context)? {
+            if let Some(manifest) = manifest.as_deref_mut() {
+                let name_in_archive = normalize_path(&entry.name_in_archive);
+                if name_in_archive == MANIFEST_ENTRY_NAME {
+                    return Err(anyhow::Error::msg(format!(
+                        "{} is reserved for the manifest of signed bundles.",
+                        MANIFEST_ENTRY_NAME
+                    )));
                 }
+                manifest.add(&name_in_archive, &entry.data);
+            }
+            append_entry(builder, &entry.name_in_archive, &entry.data)?;
+            if let Some(cache_writer) = cache_writer.as_deref_mut() {
+                cache_writer.add(&entry.relative_path, &entry.stamp, &entry.name_in_archive, &entry.data)?;
             }
-            builder.append_file(name_in_archive, &mut file)?;
         }
     }
     Ok(())
 }
 
+const ENCODE_BATCH_SIZE: usize = 256;
+
+fn append_entry(builder: &mut tar::Builder<fs::File>, name_in_archive: &Path, data: &[u8]) -> anyhow::Result<()> {
+    // Filesystem metadata is left out, so the bundle only depends on the asset contents.
+    let mut header = tar::Header::new_gnu();
+    header.set_entry_type(tar::EntryType::Regular);
+    header.set_mode(0o644);
+    header.set_mtime(0);
+    header.set_uid(0);
+    header.set_gid(0);
+    header.set_size(data.len() as u64);
+    builder.append_data(&mut header, name_in_archive, data)?;
+    Ok(())
+}
+
+#[cfg(feature = "parallel")]
+fn encode_batch(files: &[PathBuf], context: &EncodeContext) -> anyhow::Result<Vec<EncodedEntry>> {
+    use rayon::prelude::*;
+
+    files.par_iter().map(|path| encode_file(path, context)).collect()
+}
+
+#[cfg(not(feature = "parallel"))]
+fn encode_batch(files: &[PathBuf], context: &EncodeContext) -> anyhow::Result<Vec<EncodedEntry>> {
+    files.iter().map(|path| encode_file(path, context)).collect()
+}
+
+/// Lists the files of `asset_dir` that pass `filter` and are not ignored by an [`ASSET_IGNORE_FILE_NAME`] file.
+fn collect_files(asset_dir: &Path, filter: &FileFilter, files: &mut Vec<PathBuf>) -> anyhow::Result<()> {
+    let walker = WalkBuilder::new(asset_dir)
+        .standard_filters(false)
+        .follow_links(true)
+        .add_custom_ignore_filename(ASSET_IGNORE_FILE_NAME)
+        .build();
+    for entry_result in walker {
+        let entry_path = entry_result?.into_path();
+        if entry_path.is_dir()
+            || entry_path
+                .file_name()
+                .is_some_and(|name| name == ASSET_IGNORE_FILE_NAME)
+        {
+            continue;
+        }
+        if filter.accepts(entry_path.strip_prefix(asset_dir)?) {
+            files.push(entry_path);
+        }
+    }
+    Ok(())
+}
+
+/// Include and exclude globs of [`AssetBundler`].
+struct FileFilter {
+    include: Option<PathGlobs>,
+    exclude: PathGlobs,
+}
+
+impl FileFilter {
+    fn new(include: &[String], exclude: &[String]) -> anyhow::Result<Self> {
+        Ok(Self {
+            include: if include.is_empty() {
+                None
+            } else {
+                Some(PathGlobs::new(include)?)
+            },
+            exclude: PathGlobs::new(exclude)?,
+        })
+    }
+
+    fn accepts(&self, relative_path: &Path) -> bool {
+        self.include
+            .as_ref()
+            .map_or(true, |include| include.is_match(relative_path))
+            && !self.exclude.is_match(relative_path)
+    }
+}
+
+/// Globs matched against file names when they have no `/`, and against the whole path otherwise.
+struct PathGlobs {
+    names: GlobSet,
+    paths: GlobSet,
+}
+
+impl PathGlobs {
+    fn new(patterns: &[String]) -> anyhow::Result<Self> {
+        let mut names = GlobSetBuilder::new();
+        let mut paths = GlobSetBuilder::new();
+        for pattern in patterns {
+            if pattern.contains('/') {
+                paths.add(Glob::new(pattern.trim_start_matches('/'))?);
+            } else {
+                names.add(Glob::new(pattern)?);
+            }
+        }
+        Ok(Self {
+            names: names.build()?,
+            paths: paths.build()?,
+        })
+    }
+
+    fn is_match(&self, relative_path: &Path) -> bool {
+        self.paths.is_match(relative_path)
+            || relative_path
+                .file_name()
+                .is_some_and(|name| self.names.is_match(Path::new(name)))
+    }
+}
+
+fn encode_file(path: &Path, context: &EncodeContext) -> anyhow::Result<EncodedEntry> {
+    let relative_path = path.strip_prefix(context.prefix)?.to_owned();
+    let metadata = fs::metadata(path)?;
+    let modified = metadata
+        .modified()
+        .ok()
+        .and_then(|time| time.duration_since(UNIX_EPOCH).ok())
+        .map_or(0, |duration| duration.as_nanos());
+
+    if let Some(cache) = context.cache {
+        if let Some(cached) = cache.find_unchanged(&relative_path, metadata.len(), modified) {
+            if let Ok(data) = cache.read_data(cached) {
+                return Ok(EncodedEntry {
+                    stamp: cached.stamp.clone(),
+                    name_in_archive: cached.name_in_archive.clone(),
+                    data,
+                    relative_path,
+                });
+            }
+        }
+    }
+
+    let plain = fs::read(path)?;
+    let stamp = SourceStamp {
+        size: plain.len() as u64,
+        modified,
+        hash: Sha256::digest(&plain).into(),
+    };
+    if let Some(cache) = context.cache {
+        if let Some(cached) = cache.find_same_content(&relative_path, &stamp.hash) {
+            if let Ok(data) = cache.read_data(cached) {
+                return Ok(EncodedEntry {
+                    name_in_archive: cached.name_in_archive.clone(),
+                    data,
+                    relative_path,
+                    stamp,
+                });
+            }
+        }
+    }
+
+    let options = context.options;
+    let data = encode_entry(options, context.policy, &relative_path, &plain)?;
+    let name_in_archive = if options.encode_file_names {
+        options.try_encode_path(&relative_path)?
+    } else {
+        relative_path.clone()
+    };
+    Ok(EncodedEntry {
+        relative_path,
+        stamp,
+        name_in_archive,
+        data,
+    })
+}
+
+/// Compresses and encrypts `plain`, prefixed with the identifier of the compression used.
+fn encode_entry(
+    options: &AssetBundlingOptions,
+    policy: &CompressionPolicy,
+    path: &Path,
+    plain: &[u8],
+) -> anyhow::Result<Vec<u8>> {
+    let (compression, compressed) = policy.compress(path, plain)?;
+    let mut data = vec![compression.id()];
+    match options.try_encrypt_entry(path, compression, &compressed)? {
+        Some(encrypted) => data.extend_from_slice(&encrypted),
+        None => data.extend_from_slice(&compressed),
+    }
+    Ok(data)
+}
+
 fn get_exe_dir() -> anyhow::Result<PathBuf> {
     let mut dir = env::current_exe()?;
     dir.pop();
@@ -1,19 +1,166 @@
-use std::path::{Path, PathBuf};
+use std::{
+    fmt,
+    path::{Path, PathBuf},
+    sync::Arc,
+};
 
-use aes::{
-    cipher::{generic_array::GenericArray, KeyInit},
-    Aes128,
+use crate::{
+    bundle_header::{BundleHeader, KEY_SALT_LEN},
+    bundle_location::BundleLocation,
+    compression::Compression,
+    crypt::{derive_key, derive_nonce, fill_random, Crypter},
+    key_provider::KeyProvider,
 };
 
-use crate::crypt::{decrypt_ctr, encrypt_ctr};
+/// Cipher mode used to encrypt bundle entries and file names.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum CipherMode {
+    /// AES-CTR or plain ChaCha20, unauthenticated.
+    #[default]
+    Ctr,
+    /// AES-GCM or ChaCha20-Poly1305, every entry carries an authentication tag and modified entries are rejected.
+    Gcm,
+}
+
+/// Cipher a bundle is encrypted with, see [`EncryptionKey`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Cipher {
+    #[default]
+    Aes128,
+    Aes256,
+    ChaCha20,
+}
+
+/// Key used to encrypt bundle entries and file names, its variant selects the [`Cipher`].
+///
+/// `[u8; 16]` converts into an AES-128 key.
+#[derive(Clone, PartialEq, Eq)]
+pub enum EncryptionKey {
+    Aes128([u8; 16]),
+    Aes256([u8; 32]),
+    ChaCha20([u8; 32]),
+}
+
+impl EncryptionKey {
+    pub fn cipher(&self) -> Cipher {
+        match self {
+            EncryptionKey::Aes128(_) => Cipher::Aes128,
+            EncryptionKey::Aes256(_) => Cipher::Aes256,
+            EncryptionKey::ChaCha20(_) => Cipher::ChaCha20,
+        }
+    }
 
-#[derive(Debug, Clone)]
+    pub fn as_bytes(&self) -> &[u8] {
+        match self {
+            EncryptionKey::Aes128(key) => key,
+            EncryptionKey::Aes256(key) | EncryptionKey::ChaCha20(key) => key,
+        }
+    }
+
+    /// Creates a key for `cipher` from raw bytes, 16 for AES-128 and 32 for the others.
+    pub fn from_bytes(cipher: Cipher, bytes: &[u8]) -> anyhow::Result<Self> {
+        let wrong_length = || anyhow::Error::msg(format!("{:?} key can not be {} bytes long.", cipher, bytes.len()));
+        Ok(match cipher {
+            Cipher::Aes128 => EncryptionKey::Aes128(bytes.try_into().map_err(|_| wrong_length())?),
+            Cipher::Aes256 => EncryptionKey::Aes256(bytes.try_into().map_err(|_| wrong_length())?),
+            Cipher::ChaCha20 => EncryptionKey::ChaCha20(bytes.try_into().map_err(|_| wrong_length())?),
+        })
+    }
+
+    /// Same as [`Self::from_bytes`], with the bytes written as hex digits.
+    pub fn from_hex(cipher: Cipher, hex: &str) -> anyhow::Result<Self> {
+        let hex = hex.trim();
+        if hex.len() % 2 != 0 || !hex.is_ascii() {
+            return Err(anyhow::Error::msg("Encryption key must be hex digits."));
+        }
+        let bytes = (0..hex.len())
+            .step_by(2)
+            .map(|i| u8::from_str_radix(&hex[i..i + 2], 16))
+            .collect::<Result<Vec<_>, _>>()?;
+        Self::from_bytes(cipher, &bytes)
+    }
+
+    /// Derives a key for `cipher` from `passphrase` and `salt`.
+    pub(crate) fn derive(cipher: Cipher, passphrase: &str, salt: &[u8]) -> anyhow::Result<Self> {
+        let passphrase = passphrase.as_bytes();
+        Ok(match cipher {
+            Cipher::Aes128 => EncryptionKey::Aes128(derive_key(passphrase, salt)?),
+            Cipher::Aes256 => EncryptionKey::Aes256(derive_key(passphrase, salt)?),
+            Cipher::ChaCha20 => EncryptionKey::ChaCha20(derive_key(passphrase, salt)?),
+        })
+    }
+}
+
+impl From<[u8; 16]> for EncryptionKey {
+    fn from(key: [u8; 16]) -> Self {
+        EncryptionKey::Aes128(key)
+    }
+}
+
+/// Leaves the key bytes out, so options can be logged.
+impl fmt::Debug for EncryptionKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:?}(..)", self.cipher())
+    }
+}
+
+#[derive(Clone)]
 pub struct AssetBundlingOptions {
     pub encode_file_names: bool,
     pub encryption_on: bool,
-    pub encryption_key: Option<[u8; 16]>,
-    pub compress_on: bool,
+    pub encryption_key: Option<EncryptionKey>,
+    /// Passphrase the encryption key is derived from, together with a salt stored in the bundle header.
+    pub encryption_passphrase: Option<String>,
+    /// Environment variable `encryption_passphrase` was read from. The bundler tells cargo to rerun build.rs when it
+    /// changes.
+    pub encryption_passphrase_env: Option<String>,
+    /// Asked for the key when a bundle is loaded, instead of setting `encryption_key`.
+    pub key_provider: Option<Arc<dyn KeyProvider>>,
+    /// Cipher of keys derived from `encryption_passphrase` or returned by `key_provider` when bundling. At runtime the
+    /// cipher recorded in the bundle is used.
+    pub key_cipher: Cipher,
+    pub cipher_mode: CipherMode,
+    pub compression: Compression,
+    /// Derive nonces from the key and the encrypted data instead of generating random ones, so bundling the same
+    /// assets twice gives identical bundles. Equal entries and file name parts then encrypt to equal bytes.
+    pub deterministic_nonces: bool,
     pub asset_bundle_name: String,
+    /// Locations searched in order for `asset_bundle_name` at runtime, the first existing file is used.
+    pub bundle_search_paths: Vec<BundleLocation>,
+    /// Ed25519 public key bundles must be signed with, see
+    /// [`AssetBundler::with_signing_key`](crate::asset_bundler::AssetBundler::with_signing_key). Unsigned bundles
+    /// and entries that do not match their signed hash are then rejected.
+    pub verifying_key: Option<[u8; 32]>,
+    /// Memory map the bundle file at runtime instead of reading every entry from disk.
+    #[cfg(feature = "mmap")]
+    pub memory_map: bool,
+}
+
+/// Leaves the passphrase out, so options can be logged.
+impl fmt::Debug for AssetBundlingOptions {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut debug = f.debug_struct("AssetBundlingOptions");
+        debug
+            .field("encode_file_names", &self.encode_file_names)
+            .field("encryption_on", &self.encryption_on)
+            .field("encryption_key", &self.encryption_key)
+            .field(
+                "encryption_passphrase",
+                &self.encryption_passphrase.as_ref().map(|_| ".."),
+            )
+            .field("encryption_passphrase_env", &self.encryption_passphrase_env)
+            .field("key_provider", &self.key_provider)
+            .field("key_cipher", &self.key_cipher)
+            .field("cipher_mode", &self.cipher_mode)
+            .field("compression", &self.compression)
+            .field("deterministic_nonces", &self.deterministic_nonces)
+            .field("asset_bundle_name", &self.asset_bundle_name)
+            .field("bundle_search_paths", &self.bundle_search_paths)
+            .field("verifying_key", &self.verifying_key);
+        #[cfg(feature = "mmap")]
+        debug.field("memory_map", &self.memory_map);
+        debug.finish()
+    }
 }
 
 impl Default for AssetBundlingOptions {
@@ -22,50 +169,230 @@ impl Default for AssetBundlingOptions {
             encode_file_names: false,
             encryption_on: false,
             encryption_key: None,
-            compress_on: false,
+            encryption_passphrase: None,
+            encryption_passphrase_env: None,
+            key_provider: None,
+            key_cipher: Cipher::default(),
+            cipher_mode: CipherMode::default(),
+            compression: Compression::default(),
+            deterministic_nonces: false,
             asset_bundle_name: "assets.bin".to_owned(),
+            bundle_search_paths: vec![BundleLocation::default()],
+            verifying_key: None,
+            #[cfg(feature = "mmap")]
+            memory_map: false,
         }
     }
 }
 
 impl AssetBundlingOptions {
-    pub fn set_encryption_key(&mut self, key: [u8; 16]) -> &mut Self {
+    /// Sets the key and with it the cipher, a `[u8; 16]` array gives an AES-128 key.
+    pub fn set_encryption_key(&mut self, key: impl Into<EncryptionKey>) -> &mut Self {
+        self.encryption_on = true;
+        self.encryption_key = Some(key.into());
+        self.encryption_passphrase = None;
+        self.encryption_passphrase_env = None;
+        self.key_provider = None;
+        self
+    }
+
+    /// Derives the encryption key from `passphrase` instead of using a raw key. The bundler stores a salt in the bundle
+    /// header, and the same passphrase has to be set at runtime.
+    pub fn set_encryption_passphrase(&mut self, passphrase: impl Into<String>) -> &mut Self {
         self.encryption_on = true;
-        self.encryption_key = Some(key);
+        self.encryption_key = None;
+        self.encryption_passphrase = Some(passphrase.into());
+        self.encryption_passphrase_env = None;
+        self.key_provider = None;
+        self
+    }
+
+    /// Reads the passphrase from the environment variable `var`, so it does not have to be written in build.rs.
+    pub fn set_encryption_passphrase_from_env(&mut self, var: &str) -> anyhow::Result<&mut Self> {
+        let passphrase = std::env::var(var)
+            .map_err(|err| anyhow::Error::msg(format!("Failed to read encryption passphrase from {}: {}", var, err)))?;
+        self.set_encryption_passphrase(passphrase);
+        self.encryption_passphrase_env = Some(var.to_owned());
+        Ok(self)
+    }
+
+    /// Asks `provider` for the key of every bundle using these options when it is loaded, with
+    /// `asset_bundle_name` as the bundle id.
+    pub fn set_key_provider(&mut self, provider: impl KeyProvider + 'static) -> &mut Self {
+        self.encryption_on = true;
+        self.encryption_key = None;
+        self.encryption_passphrase = None;
+        self.encryption_passphrase_env = None;
+        self.key_provider = Some(Arc::new(provider));
+        self
+    }
+
+    /// Only loads bundles signed with the private key of `verifying_key`.
+    pub fn set_verifying_key(&mut self, verifying_key: [u8; 32]) -> &mut Self {
+        self.verifying_key = Some(verifying_key);
+        self
+    }
+
+    /// Replaces the bundle search paths with `locations`.
+    pub fn set_search_paths(&mut self, locations: impl IntoIterator<Item = BundleLocation>) -> &mut Self {
+        self.bundle_search_paths = locations.into_iter().collect();
         self
     }
 
+    /// Searches `bundle_search_paths` for the bundle file.
+    pub fn find_bundle_path(&self) -> anyhow::Result<PathBuf> {
+        let mut tried = Vec::new();
+        for location in &self.bundle_search_paths {
+            if let Some(path) = location.resolve(&self.asset_bundle_name) {
+                if path.is_file() {
+                    return Ok(path);
+                }
+                tried.push(path);
+            }
+        }
+        Err(anyhow::Error::msg(format!(
+            "Asset bundle {} not found, tried: {:?}",
+            self.asset_bundle_name, tried
+        )))
+    }
+
     pub fn is_encryption_ready(&self) -> bool {
-        self.encryption_on && self.encryption_key.is_some()
+        self.encryption_on
+            && (self.encryption_key.is_some() || self.encryption_passphrase.is_some() || self.key_provider.is_some())
     }
 
-    pub fn try_get_crypter_if_needed(&self) -> anyhow::Result<Option<Aes128>> {
+    /// Cipher of the encryption key, or `key_cipher` if it is not known yet.
+    pub fn cipher(&self) -> Cipher {
+        match &self.encryption_key {
+            Some(key) => key.cipher(),
+            None => self.key_cipher,
+        }
+    }
+
+    /// Returns a copy of these options with `encryption_key` set for `cipher`, asking the key provider or deriving it
+    /// from the passphrase and `salt`. Options with a key, or with a passphrase but no `salt`, are returned unchanged.
+    pub(crate) fn resolve_key(&self, cipher: Cipher, salt: Option<&[u8; KEY_SALT_LEN]>) -> anyhow::Result<Self> {
+        let mut options = self.clone();
+        if !self.encryption_on || self.encryption_key.is_some() {
+            return Ok(options);
+        }
+        if let Some(provider) = &self.key_provider {
+            options.encryption_key = Some(provider.key(&self.asset_bundle_name, cipher)?);
+        } else if let (Some(passphrase), Some(salt)) = (&self.encryption_passphrase, salt) {
+            options.encryption_key = Some(EncryptionKey::derive(cipher, passphrase, salt)?);
+        }
+        Ok(options)
+    }
+
+    /// Environment variables the key is read from when bundling.
+    #[cfg(feature = "bundler")]
+    pub(crate) fn key_env_vars(&self) -> Vec<String> {
+        if !self.encryption_on || self.encryption_key.is_some() {
+            return Vec::new();
+        }
+        let mut vars: Vec<_> = self.encryption_passphrase_env.iter().cloned().collect();
+        if let Some(provider) = &self.key_provider {
+            vars.extend(provider.env_vars(&self.asset_bundle_name));
+        }
+        vars
+    }
+
+    pub(crate) fn try_get_crypter_if_needed(&self, mode: CipherMode) -> Option<Crypter> {
         if self.encryption_on {
-            if let Some(aes_key) = &self.encryption_key {
-                return Ok(Some(Aes128::new(GenericArray::from_slice(aes_key))));
+            if let Some(key) = &self.encryption_key {
+                return Some(Crypter::new(key, mode));
             }
         }
-        Ok(None)
+        None
+    }
+
+    pub fn with_cipher_mode(&mut self, mode: CipherMode) -> &mut Self {
+        self.cipher_mode = mode;
+        self
     }
 
     pub fn try_encrypt(&self, plain: &[u8]) -> anyhow::Result<Option<Vec<u8>>> {
-        if let Some(crypter) = self.try_get_crypter_if_needed()? {
-            return Ok(Some(encrypt_ctr(&crypter, plain)));
+        self.try_encrypt_with_context(b"", b"", plain)
+    }
+
+    /// Encrypts the data of the bundle entry at `path`, compressed with `compression`. Authenticated modes bind it to
+    /// the path and the compression, so entries can not be swapped and their compression can not be changed.
+    #[cfg(feature = "bundler")]
+    pub(crate) fn try_encrypt_entry(
+        &self,
+        path: &Path,
+        compression: Compression,
+        plain: &[u8],
+    ) -> anyhow::Result<Option<Vec<u8>>> {
+        let context = format!("entry:{}", entry_path(path));
+        self.try_encrypt_with_context(context.as_bytes(), &entry_aad(path, compression), plain)
+    }
+
+    /// `context` is mixed into derived nonces, so equal data in different places does not share a nonce. `aad` is
+    /// authenticated along with the data in authenticated modes.
+    fn try_encrypt_with_context(&self, context: &[u8], aad: &[u8], plain: &[u8]) -> anyhow::Result<Option<Vec<u8>>> {
+        match self.try_get_crypter_if_needed(self.cipher_mode) {
+            Some(crypter) => {
+                let nonce = self.nonce(crypter.nonce_len(), context, plain)?;
+                Ok(Some(crypter.encrypt(&nonce, plain, aad)?))
+            }
+            None => Ok(None),
+        }
+    }
+
+    fn nonce(&self, len: usize, context: &[u8], plain: &[u8]) -> anyhow::Result<Vec<u8>> {
+        match &self.encryption_key {
+            Some(key) if self.deterministic_nonces => {
+                Ok(derive_nonce::<32>(key.as_bytes(), context, plain)[..len].to_vec())
+            }
+            _ => {
+                let mut nonce = vec![0; len];
+                fill_random(&mut nonce)?;
+                Ok(nonce)
+            }
         }
-        Ok(None)
     }
 
     pub fn try_decrypt(&self, encrypted: &[u8]) -> anyhow::Result<Option<Vec<u8>>> {
-        if let Some(crypter) = self.try_get_crypter_if_needed()? {
-            return Ok(Some(decrypt_ctr(&crypter, encrypted)));
+        self.try_decrypt_with_mode(self.cipher_mode, b"", encrypted)
+    }
+
+    fn try_decrypt_with_mode(&self, mode: CipherMode, aad: &[u8], encrypted: &[u8]) -> anyhow::Result<Option<Vec<u8>>> {
+        self.try_get_crypter_if_needed(mode)
+            .map(|crypter| crypter.decrypt(encrypted, aad))
+            .transpose()
+    }
+
+    /// Decrypts the data of the bundle entry at `path`, compressed with `compression`, in the layout used by the bundle
+    /// described by `header`.
+    pub(crate) fn try_decrypt_entry_for(
+        &self,
+        header: &BundleHeader,
+        path: &Path,
+        compression: Compression,
+        encrypted: &[u8],
+    ) -> anyhow::Result<Option<Vec<u8>>> {
+        if header.is_unversioned() {
+            return self.try_decrypt_for(header, encrypted);
         }
-        Ok(None)
+        self.try_decrypt_with_mode(header.cipher_mode, &entry_aad(path, compression), encrypted)
+    }
+
+    /// Same as [`Self::try_decrypt`], but reads data in the layout used by the bundle described by `header`.
+    pub(crate) fn try_decrypt_for(&self, header: &BundleHeader, encrypted: &[u8]) -> anyhow::Result<Option<Vec<u8>>> {
+        if header.is_unversioned() {
+            return self
+                .try_get_crypter_if_needed(CipherMode::Ctr)
+                .map(|crypter| crypter.decrypt_legacy(encrypted))
+                .transpose();
+        }
+        self.try_decrypt_with_mode(header.cipher_mode, b"", encrypted)
     }
 
     fn try_encode_string(&self, s: &str) -> anyhow::Result<String> {
         if self.is_encryption_ready() {
             let bytes = s.as_bytes();
-            if let Some(encrypted) = self.try_encrypt(bytes)? {
+            if let Some(encrypted) = self.try_encrypt_with_context(b"name", b"", bytes)? {
                 return Ok(bs58::encode(encrypted).into_string());
             }
         }
@@ -73,10 +400,10 @@ impl AssetBundlingOptions {
         Ok(bs58::encode(s).into_string())
     }
 
-    fn try_decode_string(&self, s: &str) -> anyhow::Result<String> {
+    fn try_decode_string(&self, header: &BundleHeader, s: &str) -> anyhow::Result<String> {
         let vec = bs58::decode(s).into_vec()?;
         if self.is_encryption_ready() {
-            if let Some(decrypted) = self.try_decrypt(&vec)? {
+            if let Some(decrypted) = self.try_decrypt_for(header, &vec)? {
                 return Ok(String::from_utf8(decrypted)?);
             }
         }
@@ -85,20 +412,39 @@ impl AssetBundlingOptions {
     }
 
     pub fn try_encode_path(&self, p: &Path) -> anyhow::Result<PathBuf> {
-        Ok(p.to_str()
-            .unwrap()
-            .replace('\\', "/")
-            .split('/')
-            .map(|part| self.try_encode_string(part).unwrap())
-            .collect())
+        split_path(p)?.map(|part| self.try_encode_string(part)).collect()
     }
 
     pub fn try_decode_path(&self, p: &Path) -> anyhow::Result<PathBuf> {
-        Ok(p.to_str()
-            .unwrap()
-            .replace('\\', "/")
-            .split('/')
-            .map(|part| self.try_decode_string(part).unwrap())
-            .collect())
+        self.try_decode_path_for(&BundleHeader::from_options(self), p)
     }
+
+    /// Same as [`Self::try_decode_path`], but reads names in the layout used by the bundle described by `header`.
+    pub(crate) fn try_decode_path_for(&self, header: &BundleHeader, p: &Path) -> anyhow::Result<PathBuf> {
+        split_path(p)?
+            .map(|part| self.try_decode_string(header, part))
+            .collect()
+    }
+}
+
+/// `path` with its components joined with `/`, the same on every platform.
+fn entry_path(path: &Path) -> String {
+    path.components()
+        .map(|component| component.as_os_str().to_string_lossy())
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+/// Associated data of the bundle entry at `path`: the identifier of its compression followed by its path.
+fn entry_aad(path: &Path, compression: Compression) -> Vec<u8> {
+    let mut aad = vec![compression.id()];
+    aad.extend_from_slice(entry_path(path).as_bytes());
+    aad
+}
+
+fn split_path(p: &Path) -> anyhow::Result<impl Iterator<Item = &str>> {
+    let p = p
+        .to_str()
+        .ok_or_else(|| anyhow::Error::msg(format!("Path {} is not valid UTF-8.", p.display())))?;
+    Ok(p.split(['/', '\\']))
 }
@@ -0,0 +1,74 @@
+use std::{
+    fs,
+    path::{Component, Path, PathBuf},
+};
+
+use crate::{
+    asset_bundling_options::AssetBundlingOptions, bundle_location::BundleLocation, bundle_reader::BundleReader,
+};
+
+/// Restores the original folder of assets from a bundle built by [`AssetBundler`](crate::asset_bundler::AssetBundler).
+pub struct AssetUnbundler {
+    pub options: AssetBundlingOptions,
+    pub output_folder: PathBuf,
+}
+
+impl Default for AssetUnbundler {
+    fn default() -> Self {
+        Self {
+            options: AssetBundlingOptions::default(),
+            output_folder: PathBuf::from("extracted_assets"),
+        }
+    }
+}
+
+impl From<AssetBundlingOptions> for AssetUnbundler {
+    fn from(options: AssetBundlingOptions) -> Self {
+        Self {
+            options,
+            ..Self::default()
+        }
+    }
+}
+
+impl AssetUnbundler {
+    /// Reads the bundle from `path` instead of searching `bundle_search_paths`.
+    pub fn with_bundle_path(&mut self, path: impl Into<PathBuf>) -> &mut Self {
+        self.options.set_search_paths([BundleLocation::Absolute(path.into())]);
+        self
+    }
+
+    pub fn with_output_folder(&mut self, path: impl Into<PathBuf>) -> &mut Self {
+        self.output_folder = path.into();
+        self
+    }
+
+    /// Extracts every entry into the output folder, returning the number of extracted files.
+    pub fn extract(&self) -> anyhow::Result<usize> {
+        let reader = BundleReader::open(self.options.clone())?;
+        let entries = reader.entries();
+        for entry in &entries {
+            let target = self.output_folder.join(safe_relative_path(&entry.path)?);
+            if let Some(parent) = target.parent() {
+                fs::create_dir_all(parent)?;
+            }
+            fs::write(&target, reader.read(&entry.path)?)?;
+        }
+        Ok(entries.len())
+    }
+}
+
+/// Rejects entry paths that would escape the output folder.
+fn safe_relative_path(path: &Path) -> anyhow::Result<&Path> {
+    if path
+        .components()
+        .all(|component| matches!(component, Component::Normal(_)))
+    {
+        Ok(path)
+    } else {
+        Err(anyhow::Error::msg(format!(
+            "Refusing to extract entry outside of the output folder: {}",
+            path.display()
+        )))
+    }
+}
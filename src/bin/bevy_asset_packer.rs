@@ -0,0 +1,354 @@
+use std::{
+    path::{Path, PathBuf},
+    process::ExitCode,
+};
+
+use bevy_asset_packer::{
+    asset_bundler::AssetBundler,
+    asset_bundling_options::{AssetBundlingOptions, Cipher, CipherMode, EncryptionKey},
+    asset_unbundler::AssetUnbundler,
+    bundle_location::BundleLocation,
+    bundle_reader::BundleReader,
+    bundle_signature,
+    compression::Compression,
+};
+use clap::{Args, Parser, Subcommand, ValueEnum};
+
+/// Pack, inspect and extract bevy asset bundles.
+#[derive(Parser)]
+#[command(version, about)]
+struct Cli {
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Pack an asset folder into a bundle.
+    Pack {
+        /// Folder with the assets to pack.
+        asset_folder: PathBuf,
+        /// Path of the bundle to write.
+        #[arg(short, long, default_value = "assets.bin")]
+        output: PathBuf,
+        /// Compression algorithm for entries.
+        #[arg(long, value_enum, default_value_t = CompressionArg::Stored)]
+        compression: CompressionArg,
+        /// Compression level, defaults to 9 for deflate and 3 for zstd.
+        #[arg(long)]
+        level: Option<i32>,
+        /// Glob of files to pack, all files are packed when none is given. Can be repeated.
+        #[arg(long, value_name = "GLOB")]
+        include: Vec<String>,
+        /// Glob of files to leave out, such as `*.psd`. Can be repeated.
+        #[arg(long, value_name = "GLOB")]
+        exclude: Vec<String>,
+        /// Glob of files to store uncompressed, such as `*.png`. Can be repeated.
+        #[arg(long, value_name = "GLOB")]
+        store: Vec<String>,
+        /// Store entries uncompressed when compression saves less than this fraction of their size.
+        #[arg(long, value_name = "FRACTION")]
+        min_saving: Option<f32>,
+        /// Encode file names, encrypting them too when a key is given.
+        #[arg(long)]
+        encode_file_names: bool,
+        /// Derive nonces from the key and data, so packing the same assets gives an identical bundle.
+        #[arg(long)]
+        deterministic: bool,
+        /// Salt for the key derived from the passphrase as 32 hex digits, a random salt is used when none is given.
+        #[arg(long, value_name = "HEX", requires = "passphrase_env")]
+        key_salt: Option<String>,
+        /// Cache file for encoded entries, so only changed assets are encoded again.
+        #[arg(long, value_name = "PATH")]
+        cache: Option<PathBuf>,
+        /// Environment variable holding an Ed25519 signing key as 64 hex digits, to sign the bundle with.
+        #[arg(long, value_name = "VAR")]
+        signing_key_env: Option<String>,
+        #[command(flatten)]
+        key: KeyArgs,
+    },
+    /// Extract every entry of a bundle into a folder.
+    Unpack {
+        bundle: PathBuf,
+        /// Folder to extract the assets into, kept apart from the source asset folder by default.
+        #[arg(short, long, default_value = "extracted_assets")]
+        output: PathBuf,
+        #[command(flatten)]
+        key: KeyArgs,
+    },
+    /// Print bundle entries with their sizes and compression ratios.
+    List {
+        bundle: PathBuf,
+        #[command(flatten)]
+        key: KeyArgs,
+    },
+    /// Decrypt and decompress every entry to check the bundle integrity.
+    Verify {
+        bundle: PathBuf,
+        #[command(flatten)]
+        key: KeyArgs,
+    },
+    /// Generate an Ed25519 key pair for signing bundles.
+    Keygen,
+}
+
+#[derive(Args)]
+struct KeyArgs {
+    /// Encryption key as 32 hex digits for AES-128, or 64 for AES-256 and ChaCha20.
+    #[arg(long)]
+    key: Option<String>,
+    /// Environment variable holding the encryption key as hex digits, keeping it out of the shell history.
+    #[arg(long, value_name = "VAR", conflicts_with = "key")]
+    key_env: Option<String>,
+    /// Cipher of the key. Packing defaults to AES-128 or AES-256 depending on the key length, reading commands to the
+    /// cipher recorded in the bundle.
+    #[arg(long, value_enum)]
+    cipher: Option<KeyCipherArg>,
+    /// Environment variable holding a passphrase to derive the encryption key from.
+    #[arg(long, value_name = "VAR", conflicts_with_all = ["key", "key_env"])]
+    passphrase_env: Option<String>,
+    /// Cipher mode used when packing.
+    #[arg(long, value_enum, default_value_t = CipherArg::Ctr)]
+    cipher_mode: CipherArg,
+    /// Ed25519 public key as 64 hex digits, the bundle has to be signed with its signing key.
+    #[arg(long, value_name = "HEX")]
+    verifying_key: Option<String>,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum CipherArg {
+    Ctr,
+    Gcm,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum KeyCipherArg {
+    Aes128,
+    Aes256,
+    Chacha20,
+}
+
+impl From<KeyCipherArg> for Cipher {
+    fn from(cipher: KeyCipherArg) -> Self {
+        match cipher {
+            KeyCipherArg::Aes128 => Cipher::Aes128,
+            KeyCipherArg::Aes256 => Cipher::Aes256,
+            KeyCipherArg::Chacha20 => Cipher::ChaCha20,
+        }
+    }
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum CompressionArg {
+    Stored,
+    Deflate,
+    Zstd,
+    Lz4,
+}
+
+impl CompressionArg {
+    fn with_level(self, level: Option<i32>) -> anyhow::Result<Compression> {
+        Ok(match self {
+            CompressionArg::Stored => Compression::Stored,
+            CompressionArg::Deflate => Compression::Deflate(u8::try_from(level.unwrap_or(9))?),
+            CompressionArg::Zstd => Compression::Zstd(level.unwrap_or(3)),
+            CompressionArg::Lz4 => Compression::Lz4,
+        })
+    }
+}
+
+impl KeyArgs {
+    /// Options for packing, or for reading a bundle encrypted with `bundle_cipher`.
+    fn options(&self, bundle_cipher: Option<Cipher>) -> anyhow::Result<AssetBundlingOptions> {
+        let mut options = AssetBundlingOptions::default();
+        options.with_cipher_mode(match self.cipher_mode {
+            CipherArg::Ctr => CipherMode::Ctr,
+            CipherArg::Gcm => CipherMode::Gcm,
+        });
+        if let Some(key) = self.raw_key()? {
+            options.set_encryption_key(parse_key(&key, self.cipher.map(Cipher::from).or(bundle_cipher))?);
+        }
+        if let Some(var) = &self.passphrase_env {
+            options.set_encryption_passphrase_from_env(var)?;
+            options.key_cipher = self.cipher.map(Cipher::from).unwrap_or_default();
+        }
+        if let Some(verifying_key) = &self.verifying_key {
+            options.set_verifying_key(parse_signature_key(verifying_key)?);
+        }
+        Ok(options)
+    }
+
+    fn reader_options(&self, bundle: &Path) -> anyhow::Result<AssetBundlingOptions> {
+        let bundle_cipher = match (self.key.is_some() || self.key_env.is_some(), self.cipher) {
+            (true, None) => BundleReader::cipher(bundle)?,
+            _ => None,
+        };
+        let mut options = self.options(bundle_cipher)?;
+        options.set_search_paths([BundleLocation::Absolute(bundle.to_path_buf())]);
+        Ok(options)
+    }
+
+    /// Hex key given with `--key` or read from the `--key-env` variable.
+    fn raw_key(&self) -> anyhow::Result<Option<String>> {
+        match (&self.key, &self.key_env) {
+            (Some(key), _) => Ok(Some(key.clone())),
+            (None, Some(var)) => Ok(Some(read_env(var, "encryption key")?)),
+            (None, None) => Ok(None),
+        }
+    }
+}
+
+/// Parses a raw key for `cipher`, guessing AES-128 or AES-256 from its length when the cipher is not known.
+fn parse_key(hex: &str, cipher: Option<Cipher>) -> anyhow::Result<EncryptionKey> {
+    let cipher = cipher.unwrap_or(if hex.trim().len() > 32 {
+        Cipher::Aes256
+    } else {
+        Cipher::Aes128
+    });
+    EncryptionKey::from_hex(cipher, hex)
+}
+
+fn parse_signature_key(hex: &str) -> anyhow::Result<[u8; 32]> {
+    from_hex(hex)
+        .and_then(|bytes| bytes.try_into().ok())
+        .ok_or_else(|| anyhow::Error::msg("Signature keys must be 64 hex digits."))
+}
+
+fn parse_key_salt(hex: &str) -> anyhow::Result<[u8; 16]> {
+    from_hex(hex)
+        .and_then(|bytes| bytes.try_into().ok())
+        .ok_or_else(|| anyhow::Error::msg("Key salts must be 32 hex digits."))
+}
+
+fn read_env(var: &str, what: &str) -> anyhow::Result<String> {
+    std::env::var(var).map_err(|err| anyhow::Error::msg(format!("Failed to read {} from {}: {}", what, var, err)))
+}
+
+/// Decodes hex digits, `None` if `hex` has an odd length or a character that is not a hex digit.
+fn from_hex(hex: &str) -> Option<Vec<u8>> {
+    let digits = hex
+        .trim()
+        .chars()
+        .map(|digit| digit.to_digit(16).map(|digit| digit as u8))
+        .collect::<Option<Vec<_>>>()?;
+    if digits.len() % 2 != 0 {
+        return None;
+    }
+    Some(digits.chunks(2).map(|pair| pair[0] << 4 | pair[1]).collect())
+}
+
+fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+fn main() -> ExitCode {
+    match run(Cli::parse().command) {
+        Ok(code) => code,
+        Err(err) => {
+            eprintln!("error: {:#}", err);
+            ExitCode::FAILURE
+        }
+    }
+}
+
+fn run(command: Command) -> anyhow::Result<ExitCode> {
+    match command {
+        Command::Pack {
+            asset_folder,
+            output,
+            compression,
+            level,
+            include,
+            exclude,
+            store,
+            min_saving,
+            encode_file_names,
+            deterministic,
+            key_salt,
+            cache,
+            signing_key_env,
+            key,
+        } => {
+            let mut options = key.options(None)?;
+            options.compression = compression.with_level(level)?;
+            options.encode_file_names = encode_file_names;
+            options.deterministic_nonces = deterministic;
+            let mut bundler = AssetBundler::from(options);
+            bundler
+                .with_asset_folder(asset_folder.to_string_lossy())
+                .with_output_path(output);
+            for pattern in include {
+                bundler.with_include(pattern);
+            }
+            for pattern in exclude {
+                bundler.with_exclude(pattern);
+            }
+            for pattern in store {
+                bundler.with_compression_rule(pattern, Compression::Stored);
+            }
+            if let Some(min_saving) = min_saving {
+                bundler.with_min_compression_saving(min_saving);
+            }
+            if let Some(cache) = cache {
+                bundler.with_cache_path(cache);
+            }
+            if let Some(key_salt) = key_salt {
+                bundler.with_key_salt(parse_key_salt(&key_salt)?);
+            }
+            if let Some(var) = signing_key_env {
+                bundler.with_signing_key(parse_signature_key(&read_env(&var, "signing key")?)?);
+            }
+            bundler.build()?;
+        }
+        Command::Unpack { bundle, output, key } => {
+            let count = AssetUnbundler::from(key.reader_options(&bundle)?)
+                .with_bundle_path(bundle)
+                .with_output_folder(output)
+                .extract()?;
+            println!("{} entries extracted", count);
+        }
+        Command::List { bundle, key } => {
+            let reader = BundleReader::open(key.reader_options(&bundle)?)?;
+            println!("{:>12} {:>12} {:>7}  path", "stored", "original", "ratio");
+            for entry in reader.entries() {
+                let original = reader.read(&entry.path)?.len() as u64;
+                let ratio = if original == 0 {
+                    1.0
+                } else {
+                    entry.stored_size as f64 / original as f64
+                };
+                println!(
+                    "{:>12} {:>12} {:>6.1}%  {}",
+                    entry.stored_size,
+                    original,
+                    ratio * 100.0,
+                    entry.path.display()
+                );
+            }
+        }
+        Command::Verify { bundle, key } => {
+            let reader = BundleReader::open(key.reader_options(&bundle)?)?;
+            let entries = reader.entries();
+            let mut failed = 0;
+            for entry in &entries {
+                if let Err(err) = reader.read(&entry.path) {
+                    eprintln!("{}", err);
+                    failed += 1;
+                }
+            }
+            println!("{} entries checked, {} failed", entries.len(), failed);
+            if failed > 0 {
+                return Ok(ExitCode::FAILURE);
+            }
+        }
+        Command::Keygen => {
+            let signing_key = bundle_signature::generate_signing_key()?;
+            println!("signing key:   {}", to_hex(&signing_key));
+            println!(
+                "verifying key: {}",
+                to_hex(&bundle_signature::verifying_key(&signing_key))
+            );
+        }
+    }
+    Ok(ExitCode::SUCCESS)
+}
@@ -0,0 +1,231 @@
+use std::{
+    collections::HashMap,
+    fs,
+    io::{BufReader, BufWriter, Read, Seek, SeekFrom, Write},
+    path::{Path, PathBuf},
+};
+
+use crate::bundle_header::KEY_SALT_LEN;
+
+const MAGIC: [u8; 4] = *b"BAPC";
+const CACHE_VERSION: u16 = 2;
+
+/// What a source file looked like when it was encoded.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct SourceStamp {
+    pub size: u64,
+    /// Modification time in nanoseconds since the unix epoch, 0 if unknown.
+    pub modified: u128,
+    pub hash: [u8; 32],
+}
+
+/// Encoded entry stored in the cache file, its data is only read when it is reused.
+pub(crate) struct CachedEntry {
+    pub stamp: SourceStamp,
+    pub name_in_archive: PathBuf,
+    data_offset: u64,
+    data_len: u64,
+}
+
+/// Encoded entries of the previous build, see [`AssetBundler::with_cache_path`](crate::asset_bundler::AssetBundler::with_cache_path).
+#[derive(Default)]
+pub(crate) struct BuildCache {
+    path: PathBuf,
+    /// Salt of the key the cached entries were encrypted with, when it was derived from a passphrase.
+    key_salt: Option<[u8; KEY_SALT_LEN]>,
+    /// Value derived from the encryption key, to drop the entries when the key changes.
+    key_check: [u8; 32],
+    entries: HashMap<String, CachedEntry>,
+}
+
+impl BuildCache {
+    /// Reads the index of the cache at `path`. A missing, unreadable or outdated cache gives an empty one, as it only
+    /// makes the build slower.
+    pub fn load(path: &Path, fingerprint: &[u8; 32]) -> Self {
+        fs::File::open(path)
+            .ok()
+            .and_then(|file| read_index(path, &mut BufReader::new(file), fingerprint).ok())
+            .unwrap_or_else(|| Self {
+                path: path.to_path_buf(),
+                ..Self::default()
+            })
+    }
+
+    pub fn key_salt(&self) -> Option<&[u8; KEY_SALT_LEN]> {
+        self.key_salt.as_ref()
+    }
+
+    /// Drops every entry if they were encrypted with another key than the one `key_check` was derived from.
+    pub fn retain_if_key_matches(&mut self, key_check: &[u8; 32]) {
+        if &self.key_check != key_check {
+            self.entries.clear();
+        }
+    }
+
+    /// Returns the entry of `relative_path` if the file still has the same size and modification time.
+    pub fn find_unchanged(&self, relative_path: &Path, size: u64, modified: u128) -> Option<&CachedEntry> {
+        self.entries
+            .get(&cache_key(relative_path))
+            .filter(|entry| modified != 0 && entry.stamp.size == size && entry.stamp.modified == modified)
+    }
+
+    /// Returns the entry of `relative_path` if the file still has the same contents.
+    pub fn find_same_content(&self, relative_path: &Path, hash: &[u8; 32]) -> Option<&CachedEntry> {
+        self.entries
+            .get(&cache_key(relative_path))
+            .filter(|entry| &entry.stamp.hash == hash)
+    }
+
+    pub fn read_data(&self, entry: &CachedEntry) -> anyhow::Result<Vec<u8>> {
+        let mut file = fs::File::open(&self.path)?;
+        file.seek(SeekFrom::Start(entry.data_offset))?;
+        let mut data = vec![0; usize::try_from(entry.data_len)?];
+        file.read_exact(&mut data)?;
+        Ok(data)
+    }
+}
+
+/// Writes a new cache next to the old one and replaces it when the build succeeds.
+pub(crate) struct BuildCacheWriter {
+    writer: BufWriter<fs::File>,
+    temp_path: PathBuf,
+    path: PathBuf,
+}
+
+impl BuildCacheWriter {
+    pub fn create(
+        path: &Path,
+        fingerprint: &[u8; 32],
+        key_salt: Option<&[u8; KEY_SALT_LEN]>,
+        key_check: &[u8; 32],
+    ) -> anyhow::Result<Self> {
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        let mut temp_path = path.as_os_str().to_owned();
+        temp_path.push(".tmp");
+        let temp_path = PathBuf::from(temp_path);
+        let mut writer = BufWriter::new(fs::File::create(&temp_path)?);
+        writer.write_all(&MAGIC)?;
+        writer.write_all(&CACHE_VERSION.to_le_bytes())?;
+        writer.write_all(fingerprint)?;
+        match key_salt {
+            Some(salt) => {
+                writer.write_all(&[1])?;
+                writer.write_all(salt)?;
+            }
+            None => writer.write_all(&[0])?,
+        }
+        writer.write_all(key_check)?;
+        Ok(Self {
+            writer,
+            temp_path,
+            path: path.to_path_buf(),
+        })
+    }
+
+    pub fn add(
+        &mut self,
+        relative_path: &Path,
+        stamp: &SourceStamp,
+        name_in_archive: &Path,
+        data: &[u8],
+    ) -> anyhow::Result<()> {
+        write_bytes(&mut self.writer, cache_key(relative_path).as_bytes())?;
+        self.writer.write_all(&stamp.size.to_le_bytes())?;
+        self.writer.write_all(&stamp.modified.to_le_bytes())?;
+        self.writer.write_all(&stamp.hash)?;
+        write_bytes(&mut self.writer, cache_key(name_in_archive).as_bytes())?;
+        write_bytes(&mut self.writer, data)?;
+        Ok(())
+    }
+
+    pub fn finish(self) -> anyhow::Result<()> {
+        let file = self.writer.into_inner().map_err(|err| err.into_error())?;
+        drop(file);
+        fs::rename(&self.temp_path, &self.path)?;
+        Ok(())
+    }
+}
+
+fn read_index(path: &Path, reader: &mut BufReader<fs::File>, fingerprint: &[u8; 32]) -> anyhow::Result<BuildCache> {
+    let mut magic = [0; 4];
+    reader.read_exact(&mut magic)?;
+    let mut version = [0; 2];
+    reader.read_exact(&mut version)?;
+    let mut stored_fingerprint = [0; 32];
+    reader.read_exact(&mut stored_fingerprint)?;
+    if magic != MAGIC || u16::from_le_bytes(version) != CACHE_VERSION || &stored_fingerprint != fingerprint {
+        return Err(anyhow::Error::msg("Build cache is outdated."));
+    }
+    let key_salt = match read_array::<1>(reader)? {
+        [0] => None,
+        _ => Some(read_array(reader)?),
+    };
+    let key_check = read_array(reader)?;
+    let file_len = reader.get_ref().metadata()?.len();
+
+    let mut entries = HashMap::new();
+    // A cache cut short by an interrupted build keeps the entries that were written completely.
+    while let Ok((relative_path, stamp, name_in_archive, data_len)) = read_entry_header(reader) {
+        let data_offset = reader.stream_position()?;
+        if data_offset.saturating_add(data_len) > file_len {
+            break;
+        }
+        reader.seek_relative(i64::try_from(data_len)?)?;
+        entries.insert(
+            relative_path,
+            CachedEntry {
+                stamp,
+                name_in_archive: PathBuf::from(name_in_archive),
+                data_offset,
+                data_len,
+            },
+        );
+    }
+    Ok(BuildCache {
+        path: path.to_path_buf(),
+        key_salt,
+        key_check,
+        entries,
+    })
+}
+
+/// Reads everything of an entry before its data: the relative path, the stamp, the name in the archive and the data
+/// length.
+fn read_entry_header(reader: &mut impl Read) -> anyhow::Result<(String, SourceStamp, String, u64)> {
+    let relative_path = String::from_utf8(read_bytes(reader)?)?;
+    let stamp = SourceStamp {
+        size: u64::from_le_bytes(read_array(reader)?),
+        modified: u128::from_le_bytes(read_array(reader)?),
+        hash: read_array(reader)?,
+    };
+    let name_in_archive = String::from_utf8(read_bytes(reader)?)?;
+    let data_len = u64::from_le_bytes(read_array(reader)?);
+    Ok((relative_path, stamp, name_in_archive, data_len))
+}
+
+fn read_array<const N: usize>(reader: &mut impl Read) -> std::io::Result<[u8; N]> {
+    let mut bytes = [0; N];
+    reader.read_exact(&mut bytes)?;
+    Ok(bytes)
+}
+
+fn read_bytes(reader: &mut impl Read) -> std::io::Result<Vec<u8>> {
+    let len = u64::from_le_bytes(read_array(reader)?);
+    let mut bytes = Vec::new();
+    reader.take(len).read_to_end(&mut bytes)?;
+    if bytes.len() as u64 != len {
+        return Err(std::io::ErrorKind::UnexpectedEof.into());
+    }
+    Ok(bytes)
+}
+
+fn write_bytes(writer: &mut impl Write, bytes: &[u8]) -> std::io::Result<()> {
+    writer.write_all(&(bytes.len() as u64).to_le_bytes())?;
+    writer.write_all(bytes)
+}
+
+fn cache_key(path: &Path) -> String {
+    path.to_string_lossy().replace('\\', "/")
+}
@@ -0,0 +1,258 @@
+use std::io::{Read, Seek, SeekFrom, Write};
+
+use crate::{
+    asset_bundling_options::{AssetBundlingOptions, Cipher, CipherMode},
+    compression::Compression,
+};
+
+const MAGIC: [u8; 4] = *b"BAPK";
+
+/// Version of the header and entry layout. Bundles of 0.4.0 have no header, they are read as version 0.
+pub(crate) const FORMAT_VERSION: u16 = 1;
+
+const FLAG_COMPRESSED: u8 = 1;
+const FLAG_ENCODED_FILE_NAMES: u8 = 1 << 1;
+const FLAG_ENCRYPTED: u8 = 1 << 2;
+const FLAG_KEY_SALT: u8 = 1 << 3;
+const FLAG_SIGNED: u8 = 1 << 4;
+
+pub(crate) const KEY_SALT_LEN: usize = 16;
+
+/// Header written in front of the tar data, describing which transforms were applied to the bundle.
+#[derive(Debug, Clone)]
+pub(crate) struct BundleHeader {
+    pub version: u16,
+    pub compressed: bool,
+    pub encoded_file_names: bool,
+    pub encrypted: bool,
+    pub cipher_mode: CipherMode,
+    pub cipher: Cipher,
+    /// Salt the encryption key was derived with, if it was derived from a passphrase.
+    pub key_salt: Option<[u8; KEY_SALT_LEN]>,
+    /// The last entry is a manifest of entry hashes, signed together with this header.
+    pub signed: bool,
+}
+
+impl BundleHeader {
+    pub fn from_options(options: &AssetBundlingOptions) -> Self {
+        Self {
+            version: FORMAT_VERSION,
+            compressed: options.compression != Compression::Stored,
+            encoded_file_names: options.encode_file_names,
+            encrypted: options.is_encryption_ready(),
+            cipher_mode: options.cipher_mode,
+            cipher: options.cipher(),
+            key_salt: None,
+            signed: false,
+        }
+    }
+
+    pub fn write(&self, writer: &mut impl Write) -> anyhow::Result<()> {
+        let mut flags = 0;
+        if self.compressed {
+            flags |= FLAG_COMPRESSED;
+        }
+        if self.encoded_file_names {
+            flags |= FLAG_ENCODED_FILE_NAMES;
+        }
+        if self.encrypted {
+            flags |= FLAG_ENCRYPTED;
+        }
+        if self.key_salt.is_some() {
+            flags |= FLAG_KEY_SALT;
+        }
+        if self.signed {
+            flags |= FLAG_SIGNED;
+        }
+
+        writer.write_all(&MAGIC)?;
+        writer.write_all(&self.version.to_le_bytes())?;
+        writer.write_all(&[flags])?;
+        writer.write_all(&[cipher_mode_id(self.cipher_mode)])?;
+        writer.write_all(&[cipher_id(self.cipher)])?;
+        if let Some(salt) = &self.key_salt {
+            writer.write_all(salt)?;
+        }
+        Ok(())
+    }
+
+    /// Reads the header in front of the tar data. Bundles written before headers existed start with the tar data
+    /// right away, they are described with the transforms of `options` and the reader is moved back to the start.
+    pub fn read(reader: &mut (impl Read + Seek), options: &AssetBundlingOptions) -> anyhow::Result<Self> {
+        let start = reader.stream_position()?;
+        let mut magic = [0; 4];
+        reader.read_exact(&mut magic)?;
+        if magic != MAGIC {
+            reader.seek(SeekFrom::Start(start))?;
+            return Ok(Self::unversioned(options));
+        }
+
+        let mut version = [0; 2];
+        reader.read_exact(&mut version)?;
+        let version = u16::from_le_bytes(version);
+        if version != FORMAT_VERSION {
+            return Err(anyhow::Error::msg(format!(
+                "Unsupported asset bundle format version {}, only version {} is supported.",
+                version, FORMAT_VERSION
+            )));
+        }
+
+        let mut bytes = [0; 3];
+        reader.read_exact(&mut bytes)?;
+        let [flags, cipher_mode, cipher] = bytes;
+        let cipher_mode = cipher_mode_from_id(cipher_mode)?;
+        let cipher = cipher_from_id(cipher)?;
+
+        let key_salt = if flags & FLAG_KEY_SALT != 0 {
+            let mut salt = [0; KEY_SALT_LEN];
+            reader.read_exact(&mut salt)?;
+            Some(salt)
+        } else {
+            None
+        };
+
+        Ok(Self {
+            version,
+            compressed: flags & FLAG_COMPRESSED != 0,
+            encoded_file_names: flags & FLAG_ENCODED_FILE_NAMES != 0,
+            encrypted: flags & FLAG_ENCRYPTED != 0,
+            cipher_mode,
+            cipher,
+            key_salt,
+            signed: flags & FLAG_SIGNED != 0,
+        })
+    }
+
+    /// Bytes of the header as written by [`Self::write`], which the bundle signature covers.
+    pub fn to_bytes(&self) -> anyhow::Result<Vec<u8>> {
+        let mut bytes = Vec::new();
+        self.write(&mut bytes)?;
+        Ok(bytes)
+    }
+
+    /// Header of a bundle without one, written by version 0.4.0 or earlier of this crate. Compression, file name
+    /// encoding and encryption were not recorded, so they are taken from the runtime `options`.
+    fn unversioned(options: &AssetBundlingOptions) -> Self {
+        Self {
+            version: 0,
+            compressed: options.compression != Compression::Stored,
+            encoded_file_names: options.encode_file_names,
+            encrypted: options.is_encryption_ready(),
+            cipher_mode: CipherMode::Ctr,
+            cipher: Cipher::Aes128,
+            key_salt: None,
+            signed: false,
+        }
+    }
+
+    /// Returns `true` for bundles of 0.4.0, which have no header. Their entries have no compression byte and are
+    /// encrypted with a fixed IV.
+    pub fn is_unversioned(&self) -> bool {
+        self.version == 0
+    }
+
+    /// Compression of every entry in bundles without a header.
+    pub fn legacy_compression(&self) -> Compression {
+        if self.compressed {
+            Compression::Deflate(9)
+        } else {
+            Compression::Stored
+        }
+    }
+
+    /// Copies the transforms recorded in this header into `options`, keeping their keys and locations.
+    pub fn apply_to(&self, options: &mut AssetBundlingOptions) {
+        options.encode_file_names = self.encoded_file_names;
+        options.cipher_mode = self.cipher_mode;
+    }
+
+    /// Checks that the runtime options can read a bundle with this header.
+    pub fn check(&self, options: &AssetBundlingOptions) -> anyhow::Result<()> {
+        check_flag("file name encoding", self.encoded_file_names, options.encode_file_names)?;
+        check_flag("encryption", self.encrypted, options.is_encryption_ready())?;
+        if self.encrypted && self.cipher_mode != options.cipher_mode {
+            return Err(anyhow::Error::msg(format!(
+                "Asset bundle was encrypted with {:?} cipher mode, but runtime options use {:?}.",
+                self.cipher_mode, options.cipher_mode
+            )));
+        }
+        if self.encrypted && self.cipher != options.cipher() {
+            return Err(anyhow::Error::msg(format!(
+                "Asset bundle was encrypted with {:?}, but the runtime key is for {:?}.",
+                self.cipher,
+                options.cipher()
+            )));
+        }
+        if options.verifying_key.is_some() && !self.signed {
+            return Err(anyhow::Error::msg(
+                "Asset bundle is not signed, but runtime options require a signature.",
+            ));
+        }
+        if self.encrypted && self.key_salt.is_some() != options.encryption_passphrase.is_some() {
+            return Err(anyhow::Error::msg(if self.key_salt.is_some() {
+                "Asset bundle key was derived from a passphrase, but runtime options have a raw key."
+            } else {
+                "Asset bundle was encrypted with a raw key, but runtime options have a passphrase."
+            }));
+        }
+        Ok(())
+    }
+}
+
+fn cipher_mode_id(mode: CipherMode) -> u8 {
+    match mode {
+        CipherMode::Ctr => 0,
+        CipherMode::Gcm => 1,
+    }
+}
+
+fn cipher_mode_from_id(id: u8) -> anyhow::Result<CipherMode> {
+    match id {
+        0 => Ok(CipherMode::Ctr),
+        1 => Ok(CipherMode::Gcm),
+        _ => Err(anyhow::Error::msg(format!(
+            "Unknown cipher mode {} in asset bundle header.",
+            id
+        ))),
+    }
+}
+
+fn cipher_id(cipher: Cipher) -> u8 {
+    match cipher {
+        Cipher::Aes128 => 0,
+        Cipher::Aes256 => 1,
+        Cipher::ChaCha20 => 2,
+    }
+}
+
+fn cipher_from_id(id: u8) -> anyhow::Result<Cipher> {
+    match id {
+        0 => Ok(Cipher::Aes128),
+        1 => Ok(Cipher::Aes256),
+        2 => Ok(Cipher::ChaCha20),
+        _ => Err(anyhow::Error::msg(format!(
+            "Unknown cipher {} in asset bundle header.",
+            id
+        ))),
+    }
+}
+
+fn check_flag(name: &str, in_bundle: bool, in_options: bool) -> anyhow::Result<()> {
+    if in_bundle == in_options {
+        return Ok(());
+    }
+    Err(anyhow::Error::msg(format!(
+        "Asset bundle was built with {} {}, but runtime options have it {}.",
+        name,
+        on_off(in_bundle),
+        on_off(in_options)
+    )))
+}
+
+fn on_off(value: bool) -> &'static str {
+    if value {
+        "enabled"
+    } else {
+        "disabled"
+    }
+}
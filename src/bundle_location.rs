@@ -0,0 +1,36 @@
+use std::{env, path::PathBuf};
+
+/// Place where [`BundledAssetIo`](crate::bundled_asset_io::BundledAssetIo) looks for a bundle file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum BundleLocation {
+    /// Exact path of the bundle file.
+    Absolute(PathBuf),
+    /// Directory relative to the directory of the running executable, which contains the bundle.
+    ExeRelative(PathBuf),
+    /// Directory relative to the current working directory, which contains the bundle.
+    CwdRelative(PathBuf),
+    /// Environment variable holding the exact path of the bundle file, skipped when it is not set.
+    EnvVar(String),
+}
+
+impl Default for BundleLocation {
+    fn default() -> Self {
+        BundleLocation::ExeRelative(PathBuf::new())
+    }
+}
+
+impl BundleLocation {
+    /// Returns the path of the bundle named `bundle_name` at this location, if it can be determined.
+    pub fn resolve(&self, bundle_name: &str) -> Option<PathBuf> {
+        match self {
+            BundleLocation::Absolute(path) => Some(path.clone()),
+            BundleLocation::ExeRelative(dir) => {
+                let mut exe_dir = env::current_exe().ok()?;
+                exe_dir.pop();
+                Some(exe_dir.join(dir).join(bundle_name))
+            }
+            BundleLocation::CwdRelative(dir) => Some(env::current_dir().ok()?.join(dir).join(bundle_name)),
+            BundleLocation::EnvVar(name) => env::var_os(name).map(PathBuf::from),
+        }
+    }
+}
@@ -0,0 +1,62 @@
+use std::{
+    fs::File,
+    path::{Path, PathBuf},
+};
+
+use crate::{
+    asset_bundling_options::{AssetBundlingOptions, Cipher},
+    bundle_header::BundleHeader,
+    mounted_bundle::MountedBundle,
+};
+
+/// Entry of a bundle as listed by [`BundleReader::entries`].
+#[derive(Debug, Clone)]
+pub struct BundleEntry {
+    /// Original path of the asset, relative to the asset folder.
+    pub path: PathBuf,
+    /// Size of the entry inside the bundle, after compression and encryption.
+    pub stored_size: u64,
+}
+
+/// Reads bundles outside of bevy, for tools and tests.
+///
+/// Compression, file name encoding and cipher mode are taken from the bundle header, so `options` only needs to
+/// provide the key and the location of the bundle. Bundles written by version 0.4.0 have no header, for them `options`
+/// also have to set the compression and file name encoding they were packed with.
+pub struct BundleReader {
+    bundle: MountedBundle,
+}
+
+impl BundleReader {
+    pub fn open(mut options: AssetBundlingOptions) -> anyhow::Result<Self> {
+        let bundle_path = options.find_bundle_path()?;
+        BundleHeader::read(&mut File::open(bundle_path)?, &options)?.apply_to(&mut options);
+
+        let mut bundle = MountedBundle::new(options, 0);
+        bundle.ensure_loaded()?;
+        Ok(Self { bundle })
+    }
+
+    /// Returns the cipher the bundle at `bundle_path` was encrypted with, `None` if it is not encrypted or was written
+    /// by version 0.4.0, which only supported AES-128.
+    pub fn cipher(bundle_path: &Path) -> anyhow::Result<Option<Cipher>> {
+        let header = BundleHeader::read(&mut File::open(bundle_path)?, &AssetBundlingOptions::default())?;
+        Ok(header.encrypted.then_some(header.cipher))
+    }
+
+    pub fn entries(&self) -> Vec<BundleEntry> {
+        self.bundle
+            .entries()
+            .into_iter()
+            .map(|(path, stored_size)| BundleEntry { path, stored_size })
+            .collect()
+    }
+
+    /// Reads the original contents of the asset at `path`.
+    pub fn read(&self, path: &Path) -> anyhow::Result<Vec<u8>> {
+        match self.bundle.load_entry(path) {
+            Some(result) => Ok(result?),
+            None => Err(anyhow::Error::msg(format!("Bundle has no entry {}", path.display()))),
+        }
+    }
+}
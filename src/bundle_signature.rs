@@ -0,0 +1,110 @@
+use std::collections::HashMap;
+
+#[cfg(feature = "bundler")]
+use ed25519_dalek::Signer;
+use ed25519_dalek::{Signature, SigningKey, VerifyingKey, SIGNATURE_LENGTH};
+use sha2::{Digest, Sha256};
+
+use crate::crypt::random_nonce;
+
+/// Name of the last archive entry of signed bundles, holding the entry hashes and their signature. Encoded file names
+/// never contain `.`, so it can only clash with an unencoded asset of the same name.
+pub(crate) const MANIFEST_ENTRY_NAME: &str = ".bundle-manifest";
+
+/// Generates a new Ed25519 signing key for [`AssetBundler::with_signing_key`](crate::asset_bundler::AssetBundler::with_signing_key).
+pub fn generate_signing_key() -> anyhow::Result<[u8; 32]> {
+    random_nonce()
+}
+
+/// Returns the public key checking bundles signed with `signing_key`, to embed in the game with
+/// [`AssetBundlingOptions::set_verifying_key`](crate::asset_bundling_options::AssetBundlingOptions::set_verifying_key).
+pub fn verifying_key(signing_key: &[u8; 32]) -> [u8; 32] {
+    SigningKey::from_bytes(signing_key).verifying_key().to_bytes()
+}
+
+/// Hash of the stored data of a bundle entry.
+pub(crate) fn entry_hash(data: &[u8]) -> [u8; 32] {
+    Sha256::digest(data).into()
+}
+
+/// Collects the hash of every entry while a bundle is written.
+#[cfg(feature = "bundler")]
+#[derive(Default)]
+pub(crate) struct ManifestWriter {
+    manifest: Vec<u8>,
+}
+
+#[cfg(feature = "bundler")]
+impl ManifestWriter {
+    pub fn add(&mut self, name_in_archive: &str, data: &[u8]) {
+        self.manifest
+            .extend_from_slice(&(name_in_archive.len() as u64).to_le_bytes());
+        self.manifest.extend_from_slice(name_in_archive.as_bytes());
+        self.manifest.extend_from_slice(&entry_hash(data));
+    }
+
+    /// Returns the manifest followed by its signature, which also covers the bundle `header`.
+    pub fn sign(mut self, signing_key: &[u8; 32], header: &[u8]) -> Vec<u8> {
+        let signature = SigningKey::from_bytes(signing_key).sign(&signed_message(header, &self.manifest));
+        self.manifest.extend_from_slice(&signature.to_bytes());
+        self.manifest
+    }
+}
+
+/// Entry hashes of a bundle whose signature was checked.
+pub(crate) struct Manifest {
+    hashes: HashMap<String, [u8; 32]>,
+}
+
+impl Manifest {
+    /// Checks the signature of the manifest entry `data` and the bundle `header` against `verifying_key`.
+    pub fn verify(verifying_key: &[u8; 32], header: &[u8], data: &[u8]) -> anyhow::Result<Self> {
+        let verifying_key = VerifyingKey::from_bytes(verifying_key)
+            .map_err(|_| anyhow::Error::msg("Verifying key is not a valid Ed25519 public key."))?;
+        let invalid = || {
+            anyhow::Error::msg("Asset bundle signature is invalid, the bundle was modified or signed with another key.")
+        };
+        if data.len() < SIGNATURE_LENGTH {
+            return Err(invalid());
+        }
+        let (manifest, signature) = data.split_at(data.len() - SIGNATURE_LENGTH);
+        let signature = Signature::from_bytes(signature.try_into()?);
+        verifying_key
+            .verify_strict(&signed_message(header, manifest), &signature)
+            .map_err(|_| invalid())?;
+
+        let mut hashes = HashMap::new();
+        let mut rest = manifest;
+        while !rest.is_empty() {
+            let name_len = usize::try_from(u64::from_le_bytes(take(&mut rest, 8)?.try_into()?))?;
+            let name = String::from_utf8(take(&mut rest, name_len)?.to_vec())?;
+            hashes.insert(name, take(&mut rest, 32)?.try_into()?);
+        }
+        Ok(Self { hashes })
+    }
+
+    /// Removes and returns the hash of the entry stored as `name_in_archive`.
+    pub fn take(&mut self, name_in_archive: &str) -> Option<[u8; 32]> {
+        self.hashes.remove(name_in_archive)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.hashes.is_empty()
+    }
+}
+
+fn signed_message(header: &[u8], manifest: &[u8]) -> Vec<u8> {
+    let mut message = Vec::with_capacity(header.len() + manifest.len());
+    message.extend_from_slice(header);
+    message.extend_from_slice(manifest);
+    message
+}
+
+fn take<'a>(bytes: &mut &'a [u8], len: usize) -> anyhow::Result<&'a [u8]> {
+    if bytes.len() < len {
+        return Err(anyhow::Error::msg("Asset bundle manifest is truncated."));
+    }
+    let (taken, rest) = bytes.split_at(len);
+    *bytes = rest;
+    Ok(taken)
+}
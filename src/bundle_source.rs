@@ -0,0 +1,130 @@
+use std::{
+    borrow::Cow,
+    fs::File,
+    io::{Cursor, Read, Seek, SeekFrom},
+    path::PathBuf,
+};
+
+pub(crate) trait ReadSeek: Read + Seek {}
+
+impl<T: Read + Seek> ReadSeek for T {}
+
+/// Reader whose positions start at `start` of `inner`, so the tar data behind the bundle header can be read as if it
+/// started the file. Tar relies on this when it seeks over entry bodies.
+pub(crate) struct OffsetReader<R> {
+    inner: R,
+    start: u64,
+}
+
+impl<R: Seek> OffsetReader<R> {
+    /// Starts at the current position of `inner`.
+    pub fn new(mut inner: R) -> std::io::Result<Self> {
+        let start = inner.stream_position()?;
+        Ok(Self { inner, start })
+    }
+
+    /// Position of the start of this reader in `inner`.
+    pub fn start(&self) -> u64 {
+        self.start
+    }
+}
+
+impl<R: Read> Read for OffsetReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        self.inner.read(buf)
+    }
+}
+
+impl<R: Seek> Seek for OffsetReader<R> {
+    fn seek(&mut self, pos: SeekFrom) -> std::io::Result<u64> {
+        let pos = match pos {
+            SeekFrom::Start(offset) => SeekFrom::Start(self.start + offset),
+            pos => pos,
+        };
+        let absolute = self.inner.seek(pos)?;
+        absolute
+            .checked_sub(self.start)
+            .ok_or_else(|| std::io::Error::new(std::io::ErrorKind::InvalidInput, "seek before the archive start"))
+    }
+}
+
+/// Storage that bundle bytes are read from.
+pub(crate) enum BundleSource {
+    File(PathBuf),
+    Static(&'static [u8]),
+    #[cfg(feature = "mmap")]
+    Mapped(memmap2::Mmap),
+}
+
+impl BundleSource {
+    /// Maps the file at `path` into memory.
+    #[cfg(feature = "mmap")]
+    pub fn map(path: &std::path::Path) -> anyhow::Result<Self> {
+        let file = File::open(path)?;
+        // SAFETY: the bundle is only read, modifying it while the game runs is undefined behavior just like
+        // replacing any other file the process has mapped.
+        let mmap = unsafe { memmap2::Mmap::map(&file)? };
+        Ok(BundleSource::Mapped(mmap))
+    }
+
+    pub fn open(&self) -> anyhow::Result<Box<dyn ReadSeek + '_>> {
+        match self {
+            BundleSource::File(path) => Ok(Box::new(File::open(path)?)),
+            BundleSource::Static(bytes) => Ok(Box::new(Cursor::new(*bytes))),
+            #[cfg(feature = "mmap")]
+            BundleSource::Mapped(mmap) => Ok(Box::new(Cursor::new(&mmap[..]))),
+        }
+    }
+
+    fn bytes(&self) -> Option<&[u8]> {
+        match self {
+            BundleSource::File(_) => None,
+            BundleSource::Static(bytes) => Some(bytes),
+            #[cfg(feature = "mmap")]
+            BundleSource::Mapped(mmap) => Some(mmap),
+        }
+    }
+
+    /// Length of the bundle in bytes.
+    pub fn size(&self) -> anyhow::Result<u64> {
+        match self.bytes() {
+            Some(bytes) => Ok(bytes.len() as u64),
+            None => {
+                let BundleSource::File(path) = self else {
+                    unreachable!("only file sources are not kept in memory")
+                };
+                Ok(std::fs::metadata(path)?.len())
+            }
+        }
+    }
+
+    /// Reads `size` bytes starting at `offset` from the start of the bundle, borrowing them when the bundle is
+    /// already in memory.
+    pub fn read_range(&self, offset: u64, size: u64) -> anyhow::Result<Cow<'_, [u8]>> {
+        let vec = match self.bytes() {
+            Some(bytes) => {
+                let start = bytes.len().min(offset as usize);
+                let end = bytes.len().min(start.saturating_add(size as usize));
+                Cow::Borrowed(&bytes[start..end])
+            }
+            None => {
+                let BundleSource::File(path) = self else {
+                    unreachable!("only file sources are not kept in memory")
+                };
+                let mut file = File::open(path)?;
+                file.seek(SeekFrom::Start(offset))?;
+                let mut vec = Vec::new();
+                file.take(size).read_to_end(&mut vec)?;
+                Cow::Owned(vec)
+            }
+        };
+        if vec.len() as u64 != size {
+            return Err(anyhow::Error::msg(format!(
+                "Asset bundle ended after {} of {} entry bytes.",
+                vec.len(),
+                size
+            )));
+        }
+        Ok(vec)
+    }
+}
@@ -1,163 +1,202 @@
-use std::{
-    borrow::Borrow,
-    collections::HashMap,
-    env,
-    fs::File,
-    io::prelude::*,
-    path::{Path, PathBuf},
-    sync::{Arc, RwLock},
-};
+use std::path::{Path, PathBuf};
 
 use bevy::{
     asset::{AssetIo, AssetIoError, ChangeWatcher},
     utils::BoxedFuture,
 };
-use miniz_oxide::inflate::decompress_to_vec;
-use tar::Archive;
 
-use crate::{asset_bundling_options::AssetBundlingOptions, path_info::ArchivePathInfo};
+use crate::{
+    asset_bundling_options::AssetBundlingOptions,
+    mounted_bundle::{normalize_path, MountedBundle},
+};
 
-type ParentDirToPathInfo = HashMap<String, Vec<ArchivePathInfo>>;
+/// Order in which the bundle and the fallback [`AssetIo`] are searched.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum LayerOrder {
+    /// Use the bundle, and the fallback only for assets missing from it.
+    #[default]
+    BundleFirst,
+    /// Use the fallback, so loose files override bundled ones.
+    FallbackFirst,
+}
 
-#[derive(Default)]
 pub struct BundledAssetIo {
-    options: AssetBundlingOptions,
-    parent_dir_to_path_info: Option<Arc<RwLock<ParentDirToPathInfo>>>,
+    bundles: Vec<MountedBundle>,
+    fallback: Option<Box<dyn AssetIo>>,
+    layer_order: LayerOrder,
+}
+
+impl Default for BundledAssetIo {
+    fn default() -> Self {
+        Self::from(AssetBundlingOptions::default())
+    }
 }
 
 impl From<AssetBundlingOptions> for BundledAssetIo {
     fn from(options: AssetBundlingOptions) -> Self {
         Self {
-            options,
-            parent_dir_to_path_info: None,
+            bundles: vec![MountedBundle::new(options, 0)],
+            fallback: None,
+            layer_order: LayerOrder::default(),
         }
     }
 }
 
 impl BundledAssetIo {
+    /// Mounts another bundle. Entries of bundles with higher `priority` shadow entries with the same path in lower
+    /// ones, bundles with equal priority are shadowed by the ones mounted later.
+    pub fn with_bundle(&mut self, options: AssetBundlingOptions, priority: i32) -> &mut Self {
+        self.insert_bundle(MountedBundle::new(options, priority));
+        self
+    }
+
+    fn insert_bundle(&mut self, bundle: MountedBundle) {
+        let index = self
+            .bundles
+            .iter()
+            .position(|mounted| mounted.priority() <= bundle.priority())
+            .unwrap_or(self.bundles.len());
+        self.bundles.insert(index, bundle);
+    }
+
+    /// Creates an asset io reading its main bundle from memory, see [`include_asset_bundle`](crate::include_asset_bundle).
+    pub fn from_embedded(options: AssetBundlingOptions, bytes: &'static [u8]) -> Self {
+        Self {
+            bundles: vec![MountedBundle::embedded(options, bytes, 0)],
+            ..Self::default()
+        }
+    }
+
+    /// Mounts a bundle read from memory, see [`Self::with_bundle`] for the meaning of `priority`.
+    pub fn with_embedded_bundle(
+        &mut self,
+        options: AssetBundlingOptions,
+        bytes: &'static [u8],
+        priority: i32,
+    ) -> &mut Self {
+        self.insert_bundle(MountedBundle::embedded(options, bytes, priority));
+        self
+    }
+
+    /// Layers another [`AssetIo`], such as `FileAssetIo`, with the bundles.
+    pub fn with_fallback(&mut self, io: impl AssetIo, order: LayerOrder) -> &mut Self {
+        self.fallback = Some(Box::new(io));
+        self.layer_order = order;
+        self
+    }
+
+    /// Loads the index of every mounted bundle, returning the first error after trying all of them.
     pub fn ensure_loaded(&mut self) -> anyhow::Result<()> {
-        if self.parent_dir_to_path_info.is_none() {
-            let bundle_path = self.get_bundle_path()?;
-            let file = File::open(bundle_path)?;
-            let mut archive = Archive::new(file);
-            let mut mappings: ParentDirToPathInfo = HashMap::new();
-            for entry in archive.entries()?.flatten() {
-                let path = entry.path()?;
-                let decoded_path = if self.options.encode_file_names {
-                    self.options.try_decode_path(path.borrow())?
-                } else {
-                    path.to_path_buf()
-                };
-                let mut parent_dir = decoded_path.clone();
-                let parent_dir_str = if parent_dir.pop() {
-                    normalize_path(&parent_dir)
-                } else {
-                    "".into()
-                };
-                let path_info = ArchivePathInfo::new(decoded_path);
-                if let Some(vec) = mappings.get_mut(&parent_dir_str) {
-                    vec.push(path_info);
-                } else {
-                    mappings.insert(parent_dir_str, vec![path_info]);
+        let mut result = Ok(());
+        for bundle in &mut self.bundles {
+            if let Err(err) = bundle.ensure_loaded() {
+                if result.is_ok() {
+                    result = Err(err.context(format!("Failed to load {}", bundle.options().asset_bundle_name)));
                 }
             }
-            self.parent_dir_to_path_info = Some(Arc::new(RwLock::new(mappings)));
-            Ok(())
-        } else {
-            Err(anyhow::Error::msg("Entity file is not found"))
         }
+        result
     }
 
-    fn get_bundle_path(&self) -> anyhow::Result<PathBuf, AssetIoError> {
-        let mut bundle_path = env::current_exe().map_err(AssetIoError::Io)?;
-        bundle_path.pop();
-        bundle_path.push(self.options.asset_bundle_name.clone());
-        Ok(bundle_path)
+    fn load_from_bundles(&self, path: &Path) -> Result<Vec<u8>, AssetIoError> {
+        for bundle in &self.bundles {
+            match bundle.load(path) {
+                Err(AssetIoError::NotFound(_)) => continue,
+                result => return result,
+            }
+        }
+        Err(AssetIoError::NotFound(path.to_path_buf()))
+    }
+
+    fn read_bundle_directory(&self, path: &Path) -> Result<Vec<PathBuf>, AssetIoError> {
+        let mut result = Err(AssetIoError::NotFound(path.to_path_buf()));
+        for bundle in &self.bundles {
+            if let Ok(entries) = bundle.read_directory(path) {
+                result = Ok(merge_paths(result.unwrap_or_default(), entries));
+            }
+        }
+        result
+    }
+
+    fn get_bundle_metadata(&self, path: &Path) -> Result<bevy::asset::Metadata, AssetIoError> {
+        self.bundles
+            .iter()
+            .find_map(|bundle| bundle.get_metadata(path).ok())
+            .ok_or_else(|| AssetIoError::NotFound(path.to_path_buf()))
     }
 }
 
 impl AssetIo for BundledAssetIo {
     fn load_path<'a>(&'a self, path: &'a Path) -> BoxedFuture<'a, Result<Vec<u8>, AssetIoError>> {
         Box::pin(async move {
-            let bundle_path = self.get_bundle_path()?;
-            let file = File::open(bundle_path)?;
-            let encoded_entry_path = if self.options.encode_file_names {
-                self.options.try_encode_path(path).map_err(map_error)?
-            } else {
-                PathBuf::from(normalize_path(path))
-            };
-            let mut archive = Archive::new(file);
-            for mut entry in archive.entries()?.flatten() {
-                let entry_path = entry.path()?;
-                if entry_path.eq(&encoded_entry_path) {
-                    let mut vec = Vec::new();
-                    entry.read_to_end(&mut vec)?;
-
-                    if let Some(decrypted) = self.options.try_decrypt(&vec).map_err(map_error)? {
-                        if self.options.compress_on {
-                            let decompressed = decompress_to_vec(&decrypted).unwrap_or_default();
-                            return Ok(decompressed);
-                        }
-                        return Ok(decrypted);
-                    }
-                    if self.options.compress_on {
-                        let decompressed = decompress_to_vec(&vec).unwrap_or_default();
-                        return Ok(decompressed);
-                    }
-
-                    return Ok(vec);
-                }
+            match (&self.fallback, self.layer_order) {
+                (None, _) => self.load_from_bundles(path),
+                (Some(fallback), LayerOrder::BundleFirst) => match self.load_from_bundles(path) {
+                    Err(AssetIoError::NotFound(_)) => fallback.load_path(path).await,
+                    result => result,
+                },
+                (Some(fallback), LayerOrder::FallbackFirst) => match fallback.load_path(path).await {
+                    Err(AssetIoError::NotFound(_)) => self.load_from_bundles(path),
+                    result => result,
+                },
             }
-            Err(AssetIoError::NotFound(path.to_path_buf()))
         })
     }
 
     fn read_directory(&self, path: &Path) -> Result<Box<dyn Iterator<Item = PathBuf>>, AssetIoError> {
-        if let Some(lock) = self.parent_dir_to_path_info.clone() {
-            let mappings = lock.read().unwrap();
-            let path_str = normalize_path(path);
-            if let Some(entries) = mappings.get(&path_str) {
-                let vec: Vec<_> = entries.iter().map(|e| e.path()).collect();
-                return Ok(Box::new(vec.into_iter()));
-            }
-        }
-        Err(AssetIoError::NotFound(path.to_path_buf()))
+        let bundled = self.read_bundle_directory(path);
+        let Some(fallback) = &self.fallback else {
+            return bundled.map(|vec| Box::new(vec.into_iter()) as Box<dyn Iterator<Item = PathBuf>>);
+        };
+        let loose = fallback.read_directory(path).map(|iter| iter.collect::<Vec<_>>());
+        let (first, second) = match self.layer_order {
+            LayerOrder::BundleFirst => (bundled, loose),
+            LayerOrder::FallbackFirst => (loose, bundled),
+        };
+
+        let merged = match (first, second) {
+            (Err(err), Err(_)) => return Err(err),
+            (Ok(vec), Err(_)) | (Err(_), Ok(vec)) => vec,
+            (Ok(vec), Ok(other)) => merge_paths(vec, other),
+        };
+        Ok(Box::new(merged.into_iter()))
     }
 
-    fn watch_path_for_changes(&self, _to_watch: &Path, _to_reload: Option<PathBuf>) -> Result<(), AssetIoError> {
+    fn watch_path_for_changes(&self, to_watch: &Path, to_reload: Option<PathBuf>) -> Result<(), AssetIoError> {
+        if let Some(fallback) = &self.fallback {
+            return fallback.watch_path_for_changes(to_watch, to_reload);
+        }
         Ok(())
     }
 
-    fn watch_for_changes(&self, _configuration: &ChangeWatcher) -> Result<(), AssetIoError> {
+    fn watch_for_changes(&self, configuration: &ChangeWatcher) -> Result<(), AssetIoError> {
+        if let Some(fallback) = &self.fallback {
+            return fallback.watch_for_changes(configuration);
+        }
         Ok(())
     }
 
     fn get_metadata(&self, path: &Path) -> Result<bevy::asset::Metadata, AssetIoError> {
-        if let Some(lock) = self.parent_dir_to_path_info.clone() {
-            let mappings = lock.read().unwrap();
-            let path_str = normalize_path(path);
-            if mappings.contains_key(&path_str) {
-                Ok(bevy::asset::Metadata::new(bevy::asset::FileType::Directory))
-            } else {
-                for v in mappings.values() {
-                    for info in v {
-                        if info.path() == path {
-                            return Ok(bevy::asset::Metadata::new(bevy::asset::FileType::File));
-                        }
-                    }
-                }
-                Err(AssetIoError::NotFound(path.to_path_buf()))
-            }
-        } else {
-            Err(AssetIoError::NotFound(path.to_path_buf()))
+        let Some(fallback) = &self.fallback else {
+            return self.get_bundle_metadata(path);
+        };
+        match self.layer_order {
+            LayerOrder::BundleFirst => self
+                .get_bundle_metadata(path)
+                .or_else(|err| fallback.get_metadata(path).map_err(|_| err)),
+            LayerOrder::FallbackFirst => fallback
+                .get_metadata(path)
+                .or_else(|err| self.get_bundle_metadata(path).map_err(|_| err)),
         }
     }
 }
 
-fn map_error(err: anyhow::Error) -> AssetIoError {
-    AssetIoError::Io(std::io::Error::new(std::io::ErrorKind::Other, format!("{}", err)))
-}
-
-fn normalize_path(path: &Path) -> String {
-    path.to_str().unwrap_or("").replace('\\', "/")
+/// Appends paths from `other` that are not in `paths` yet.
+fn merge_paths(mut paths: Vec<PathBuf>, other: Vec<PathBuf>) -> Vec<PathBuf> {
+    for path in other {
+        if !paths.iter().any(|p| normalize_path(p) == normalize_path(&path)) {
+            paths.push(path);
+        }
+    }
+    paths
 }
@@ -1,29 +1,117 @@
 use bevy::{
     app::{App, Plugin},
     asset::AssetServer,
+    ecs::{event::Event, system::Resource},
+    log::warn,
 };
 
-use crate::{asset_bundling_options::AssetBundlingOptions, bundled_asset_io::BundledAssetIo};
+#[cfg(not(any(target_arch = "wasm32", target_os = "android")))]
+use bevy::asset::FileAssetIo;
+
+use crate::{
+    asset_bundling_options::AssetBundlingOptions,
+    bundled_asset_io::{BundledAssetIo, LayerOrder},
+};
+
+/// What [`BundledAssetIoPlugin`] does when the asset bundle can not be loaded.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum BundleLoadFailurePolicy {
+    /// Panic with the load error.
+    #[default]
+    Panic,
+    /// Skip installing the bundle, so `AssetPlugin` loads loose files from the `assets/` folder.
+    FallbackToFilesystem,
+    /// Install an empty bundle, insert [`BundleLoadError`] as a resource and send it as an event.
+    Report,
+}
+
+/// Reason why the asset bundle could not be loaded, see [`BundleLoadFailurePolicy::Report`].
+#[derive(Event, Resource, Debug, Clone)]
+pub struct BundleLoadError {
+    pub message: String,
+}
 
 #[derive(Default)]
 pub struct BundledAssetIoPlugin {
     options: AssetBundlingOptions,
+    embedded: Option<&'static [u8]>,
+    extra_bundles: Vec<(AssetBundlingOptions, i32)>,
+    failure_policy: BundleLoadFailurePolicy,
+    filesystem_layer: Option<(String, LayerOrder)>,
 }
 
 impl From<AssetBundlingOptions> for BundledAssetIoPlugin {
     fn from(options: AssetBundlingOptions) -> Self {
-        Self { options }
+        Self {
+            options,
+            embedded: None,
+            extra_bundles: Vec::new(),
+            failure_policy: BundleLoadFailurePolicy::default(),
+            filesystem_layer: None,
+        }
+    }
+}
+
+impl BundledAssetIoPlugin {
+    /// Reads the main bundle from memory, see [`include_asset_bundle`](crate::include_asset_bundle).
+    pub fn from_embedded(options: AssetBundlingOptions, bytes: &'static [u8]) -> Self {
+        Self {
+            embedded: Some(bytes),
+            ..Self::from(options)
+        }
+    }
+
+    /// Mounts another bundle on top of the main one, see [`BundledAssetIo::with_bundle`].
+    pub fn with_bundle(mut self, options: AssetBundlingOptions, priority: i32) -> Self {
+        self.extra_bundles.push((options, priority));
+        self
+    }
+
+    pub fn with_failure_policy(mut self, policy: BundleLoadFailurePolicy) -> Self {
+        self.failure_policy = policy;
+        self
+    }
+
+    /// Layers loose files from `asset_folder` with the bundle, so they can be overridden without rebuilding it.
+    #[cfg(not(any(target_arch = "wasm32", target_os = "android")))]
+    pub fn with_filesystem_layer(mut self, asset_folder: impl Into<String>, order: LayerOrder) -> Self {
+        self.filesystem_layer = Some((asset_folder.into(), order));
+        self
     }
 }
 
 impl Plugin for BundledAssetIoPlugin {
     fn build(&self, app: &mut App) {
-        let mut io = BundledAssetIo::from(self.options.clone());
-        match io.ensure_loaded() {
-            _ => {
-                app.insert_resource(AssetServer::new(io));
+        app.add_event::<BundleLoadError>();
+
+        let mut io = match self.embedded {
+            Some(bytes) => BundledAssetIo::from_embedded(self.options.clone(), bytes),
+            None => BundledAssetIo::from(self.options.clone()),
+        };
+        for (options, priority) in &self.extra_bundles {
+            io.with_bundle(options.clone(), *priority);
+        }
+        #[cfg(not(any(target_arch = "wasm32", target_os = "android")))]
+        if let Some((asset_folder, order)) = &self.filesystem_layer {
+            io.with_fallback(FileAssetIo::new(asset_folder, &None), *order);
+        }
+        if let Err(err) = io.ensure_loaded() {
+            let message = format!("Failed to load asset bundles: {:#}", err);
+            match self.failure_policy {
+                BundleLoadFailurePolicy::Panic => panic!("{}", message),
+                BundleLoadFailurePolicy::FallbackToFilesystem => {
+                    warn!("{}, falling back to the filesystem.", message);
+                    return;
+                }
+                BundleLoadFailurePolicy::Report => {
+                    warn!("{}", message);
+                    let error = BundleLoadError { message };
+                    app.world.send_event(error.clone());
+                    app.insert_resource(error);
+                }
             }
         }
+        app.insert_resource(AssetServer::new(io));
     }
 
     fn name(&self) -> &str {
@@ -0,0 +1,148 @@
+#[cfg(feature = "bundler")]
+use std::path::Path;
+
+#[cfg(feature = "bundler")]
+use globset::{Glob, GlobSet, GlobSetBuilder};
+
+/// Algorithm used to compress bundle entries.
+///
+/// Every variant is always available in the API, but all except [`Compression::Stored`] need their cargo feature
+/// (`deflate`, `zstd` or `lz4`) to be enabled both when bundling and at runtime.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Compression {
+    /// Store entries as is.
+    #[default]
+    Stored,
+    /// DEFLATE with a level from 0 to 10.
+    Deflate(u8),
+    /// Zstandard with a level from 1 to 22.
+    Zstd(i32),
+    /// LZ4, fastest to decompress.
+    Lz4,
+}
+
+impl Compression {
+    /// Identifier stored in front of every entry.
+    pub(crate) fn id(&self) -> u8 {
+        match self {
+            Compression::Stored => 0,
+            Compression::Deflate(_) => 1,
+            Compression::Zstd(_) => 2,
+            Compression::Lz4 => 3,
+        }
+    }
+
+    /// Returns the compression with identifier `id`, at its default level.
+    pub(crate) fn from_id(id: u8) -> anyhow::Result<Self> {
+        match id {
+            0 => Ok(Compression::Stored),
+            1 => Ok(Compression::Deflate(9)),
+            2 => Ok(Compression::Zstd(3)),
+            3 => Ok(Compression::Lz4),
+            _ => Err(anyhow::Error::msg(format!(
+                "Unknown compression {} in bundle entry.",
+                id
+            ))),
+        }
+    }
+
+    pub fn compress(&self, plain: &[u8]) -> anyhow::Result<Vec<u8>> {
+        match self {
+            Compression::Stored => Ok(plain.to_vec()),
+            #[cfg(feature = "deflate")]
+            Compression::Deflate(level) => Ok(miniz_oxide::deflate::compress_to_vec(plain, *level)),
+            #[cfg(feature = "zstd")]
+            Compression::Zstd(level) => Ok(zstd::encode_all(plain, *level)?),
+            #[cfg(feature = "lz4")]
+            Compression::Lz4 => Ok(lz4_flex::compress_prepend_size(plain)),
+            #[allow(unreachable_patterns)]
+            _ => Err(self.missing_feature()),
+        }
+    }
+
+    pub fn decompress(&self, compressed: &[u8]) -> anyhow::Result<Vec<u8>> {
+        match self {
+            Compression::Stored => Ok(compressed.to_vec()),
+            #[cfg(feature = "deflate")]
+            Compression::Deflate(_) => miniz_oxide::inflate::decompress_to_vec(compressed).map_err(anyhow::Error::msg),
+            #[cfg(feature = "zstd")]
+            Compression::Zstd(_) => Ok(zstd::decode_all(compressed)?),
+            #[cfg(feature = "lz4")]
+            Compression::Lz4 => Ok(lz4_flex::decompress_size_prepended(compressed)?),
+            #[allow(unreachable_patterns)]
+            _ => Err(self.missing_feature()),
+        }
+    }
+
+    fn missing_feature(&self) -> anyhow::Error {
+        let feature = match self {
+            Compression::Stored => "",
+            Compression::Deflate(_) => "deflate",
+            Compression::Zstd(_) => "zstd",
+            Compression::Lz4 => "lz4",
+        };
+        anyhow::Error::msg(format!(
+            "{:?} compression requires the `{}` feature of bevy_asset_packer.",
+            self, feature
+        ))
+    }
+}
+
+/// Overrides the compression of bundle entries whose path inside the asset folder matches `pattern`, see
+/// [`AssetBundler::with_compression_rule`](crate::asset_bundler::AssetBundler::with_compression_rule).
+#[cfg(feature = "bundler")]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CompressionRule {
+    /// Glob such as `*.png` or `music/**`.
+    pub pattern: String,
+    pub compression: Compression,
+}
+
+/// Picks the compression of every entry while bundling.
+#[cfg(feature = "bundler")]
+pub(crate) struct CompressionPolicy {
+    default: Compression,
+    rules: GlobSet,
+    rule_compressions: Vec<Compression>,
+    min_saving: Option<f32>,
+}
+
+#[cfg(feature = "bundler")]
+impl CompressionPolicy {
+    pub fn new(default: Compression, rules: &[CompressionRule], min_saving: Option<f32>) -> anyhow::Result<Self> {
+        let mut builder = GlobSetBuilder::new();
+        for rule in rules {
+            builder.add(Glob::new(&rule.pattern)?);
+        }
+        Ok(Self {
+            default,
+            rules: builder.build()?,
+            rule_compressions: rules.iter().map(|rule| rule.compression).collect(),
+            min_saving,
+        })
+    }
+
+    /// Compression of the first rule matching `path`, or the default one.
+    pub fn select(&self, path: &Path) -> Compression {
+        self.rules
+            .matches(path)
+            .into_iter()
+            .min()
+            .map_or(self.default, |index| self.rule_compressions[index])
+    }
+
+    /// Compresses the entry at `path`, falling back to [`Compression::Stored`] when it saves too little.
+    pub fn compress(&self, path: &Path, plain: &[u8]) -> anyhow::Result<(Compression, Vec<u8>)> {
+        let compression = self.select(path);
+        let compressed = compression.compress(plain)?;
+        match self.min_saving {
+            Some(min_saving)
+                if compression != Compression::Stored
+                    && compressed.len() as f64 > plain.len() as f64 * (1.0 - min_saving as f64) =>
+            {
+                Ok((Compression::Stored, plain.to_vec()))
+            }
+            _ => Ok((compression, compressed)),
+        }
+    }
+}
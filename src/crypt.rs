@@ -1,5 +1,30 @@
-use aes::{cipher::BlockEncrypt, Aes128};
+use aes::{
+    cipher::{consts::U16, BlockEncrypt, KeyInit, KeyIvInit, StreamCipher},
+    Aes128, Aes256,
+};
+use aes_gcm::{
+    aead::{Aead, Payload},
+    Aes128Gcm, Aes256Gcm,
+};
+use argon2::{Algorithm, Argon2, Params, Version};
 use belt_ctr::cipher::generic_array::GenericArray;
+use chacha20::ChaCha20;
+use chacha20poly1305::ChaCha20Poly1305;
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
+
+use crate::{
+    asset_bundling_options::{CipherMode, EncryptionKey},
+    error::IntegrityError,
+};
+
+/// Nonce length of AES-CTR.
+pub const NONCE_LEN: usize = 16;
+/// Nonce length of AES-GCM, ChaCha20 and ChaCha20-Poly1305.
+pub const GCM_NONCE_LEN: usize = 12;
+
+/// IV used for every entry by bundles of 0.4.0, which have no header.
+const LEGACY_IV: [u8; NONCE_LEN] = [57, 206, 202, 7, 215, 17, 43, 219, 131, 171, 7, 214, 85, 12, 129, 176];
 
 fn increment_counter(counter: &mut [u8]) {
     for byte in counter.iter_mut().rev() {
@@ -10,29 +35,171 @@ fn increment_counter(counter: &mut [u8]) {
     }
 }
 
-pub fn encrypt_ctr(cipher: &Aes128, plaintext: &[u8]) -> Vec<u8> {
-    let mut ciphertext = Vec::with_capacity(plaintext.len());
-
-    let iv = [57, 206, 202, 7, 215, 17, 43, 219, 131, 171, 7, 214, 85, 12, 129, 176];
-
-    let mut counter = iv.to_vec();
-    counter.resize(16, 0);
+fn apply_keystream<C: BlockEncrypt<BlockSize = U16>>(cipher: &C, iv: &[u8; NONCE_LEN], input: &[u8]) -> Vec<u8> {
+    let mut output = Vec::with_capacity(input.len());
 
+    let mut counter = *iv;
     let mut block = GenericArray::clone_from_slice(&counter);
 
-    for chunk in plaintext.chunks(16) {
+    for chunk in input.chunks(16) {
         cipher.encrypt_block(&mut block);
 
         let encrypted_chunk: Vec<u8> = block.iter().zip(chunk.iter()).map(|(b1, b2)| b1 ^ b2).collect();
 
-        ciphertext.extend_from_slice(&encrypted_chunk);
+        output.extend_from_slice(&encrypted_chunk);
         increment_counter(&mut counter);
         block = GenericArray::clone_from_slice(&counter);
     }
 
-    ciphertext
+    output
+}
+
+pub fn fill_random(bytes: &mut [u8]) -> anyhow::Result<()> {
+    getrandom::getrandom(bytes).map_err(|err| anyhow::Error::msg(format!("Failed to generate nonce: {}", err)))
+}
+
+pub fn random_nonce<const N: usize>() -> anyhow::Result<[u8; N]> {
+    let mut nonce = [0; N];
+    fill_random(&mut nonce)?;
+    Ok(nonce)
+}
+
+/// Derives a nonce from `key`, `context` and `plaintext`, so encrypting the same value twice gives the same result.
+pub fn derive_nonce<const N: usize>(key: &[u8], context: &[u8], plaintext: &[u8]) -> [u8; N] {
+    let mut mac = <Hmac<Sha256> as Mac>::new_from_slice(key).expect("HMAC accepts keys of any length");
+    mac.update(&(context.len() as u64).to_le_bytes());
+    mac.update(context);
+    mac.update(plaintext);
+    let mut nonce = [0; N];
+    nonce.copy_from_slice(&mac.finalize().into_bytes()[..N]);
+    nonce
+}
+
+/// Argon2 memory cost in KiB. The bundle header does not record the parameters, so changing any of them makes
+/// existing passphrase bundles undecryptable.
+const ARGON2_MEMORY_KIB: u32 = 19 * 1024;
+/// Argon2 iteration count.
+const ARGON2_ITERATIONS: u32 = 2;
+/// Argon2 lanes.
+const ARGON2_PARALLELISM: u32 = 1;
+
+/// Derives an encryption key from `passphrase` and `salt` with Argon2id.
+pub fn derive_key<const N: usize>(passphrase: &[u8], salt: &[u8]) -> anyhow::Result<[u8; N]> {
+    let params = Params::new(ARGON2_MEMORY_KIB, ARGON2_ITERATIONS, ARGON2_PARALLELISM, None)
+        .map_err(|err| anyhow::Error::msg(format!("Invalid key derivation parameters: {}", err)))?;
+    let mut key = [0; N];
+    Argon2::new(Algorithm::Argon2id, Version::V0x13, params)
+        .hash_password_into(passphrase, salt, &mut key)
+        .map_err(|err| anyhow::Error::msg(format!("Failed to derive encryption key: {}", err)))?;
+    Ok(key)
+}
+
+/// Cipher instance for one key and cipher mode.
+pub enum Crypter {
+    Aes128Ctr(Aes128),
+    Aes256Ctr(Aes256),
+    ChaCha20([u8; 32]),
+    Aes128Gcm(Aes128Gcm),
+    Aes256Gcm(Aes256Gcm),
+    ChaCha20Poly1305(ChaCha20Poly1305),
+}
+
+impl Crypter {
+    pub fn new(key: &EncryptionKey, mode: CipherMode) -> Self {
+        match (key, mode) {
+            (EncryptionKey::Aes128(key), CipherMode::Ctr) => Crypter::Aes128Ctr(Aes128::new(key.into())),
+            (EncryptionKey::Aes256(key), CipherMode::Ctr) => Crypter::Aes256Ctr(Aes256::new(key.into())),
+            (EncryptionKey::ChaCha20(key), CipherMode::Ctr) => Crypter::ChaCha20(*key),
+            (EncryptionKey::Aes128(key), CipherMode::Gcm) => Crypter::Aes128Gcm(Aes128Gcm::new(key.into())),
+            (EncryptionKey::Aes256(key), CipherMode::Gcm) => Crypter::Aes256Gcm(Aes256Gcm::new(key.into())),
+            (EncryptionKey::ChaCha20(key), CipherMode::Gcm) => {
+                Crypter::ChaCha20Poly1305(ChaCha20Poly1305::new(key.into()))
+            }
+        }
+    }
+
+    pub fn nonce_len(&self) -> usize {
+        match self {
+            Crypter::Aes128Ctr(_) | Crypter::Aes256Ctr(_) => NONCE_LEN,
+            _ => GCM_NONCE_LEN,
+        }
+    }
+
+    fn is_authenticated(&self) -> bool {
+        matches!(
+            self,
+            Crypter::Aes128Gcm(_) | Crypter::Aes256Gcm(_) | Crypter::ChaCha20Poly1305(_)
+        )
+    }
+
+    /// Encrypts `plaintext` with `nonce`, which is stored in front of the ciphertext. Authenticated modes append a
+    /// tag covering the ciphertext and `aad`, so modified data is rejected by [`Self::decrypt`].
+    pub fn encrypt(&self, nonce: &[u8], plaintext: &[u8], aad: &[u8]) -> anyhow::Result<Vec<u8>> {
+        if nonce.len() != self.nonce_len() {
+            return Err(anyhow::Error::msg("Nonce has the wrong length for the cipher."));
+        }
+        let sealed = match self {
+            Crypter::Aes128Ctr(cipher) => Ok(apply_keystream(cipher, nonce.try_into()?, plaintext)),
+            Crypter::Aes256Ctr(cipher) => Ok(apply_keystream(cipher, nonce.try_into()?, plaintext)),
+            Crypter::ChaCha20(key) => Ok(apply_chacha20(key, nonce, plaintext)),
+            Crypter::Aes128Gcm(cipher) => cipher.encrypt(GenericArray::from_slice(nonce), payload(plaintext, aad)),
+            Crypter::Aes256Gcm(cipher) => cipher.encrypt(GenericArray::from_slice(nonce), payload(plaintext, aad)),
+            Crypter::ChaCha20Poly1305(cipher) => {
+                cipher.encrypt(GenericArray::from_slice(nonce), payload(plaintext, aad))
+            }
+        }
+        .map_err(|_| anyhow::Error::msg("Failed to encrypt data."))?;
+
+        let mut ciphertext = Vec::with_capacity(nonce.len() + sealed.len());
+        ciphertext.extend_from_slice(nonce);
+        ciphertext.extend_from_slice(&sealed);
+        Ok(ciphertext)
+    }
+
+    /// Decrypts data produced by [`Self::encrypt`] with the same `aad`, failing with [`IntegrityError`] if
+    /// authenticated data was modified.
+    pub fn decrypt(&self, ciphertext: &[u8], aad: &[u8]) -> anyhow::Result<Vec<u8>> {
+        if ciphertext.len() < self.nonce_len() {
+            if self.is_authenticated() {
+                return Err(IntegrityError.into());
+            }
+            return Err(anyhow::Error::msg("Encrypted data is shorter than its nonce."));
+        }
+        let (nonce, data) = ciphertext.split_at(self.nonce_len());
+        let plaintext = match self {
+            Crypter::Aes128Ctr(cipher) => apply_keystream(cipher, nonce.try_into()?, data),
+            Crypter::Aes256Ctr(cipher) => apply_keystream(cipher, nonce.try_into()?, data),
+            Crypter::ChaCha20(key) => apply_chacha20(key, nonce, data),
+            Crypter::Aes128Gcm(cipher) => cipher
+                .decrypt(GenericArray::from_slice(nonce), payload(data, aad))
+                .map_err(|_| IntegrityError)?,
+            Crypter::Aes256Gcm(cipher) => cipher
+                .decrypt(GenericArray::from_slice(nonce), payload(data, aad))
+                .map_err(|_| IntegrityError)?,
+            Crypter::ChaCha20Poly1305(cipher) => cipher
+                .decrypt(GenericArray::from_slice(nonce), payload(data, aad))
+                .map_err(|_| IntegrityError)?,
+        };
+        Ok(plaintext)
+    }
+
+    /// Decrypts data of a bundle of 0.4.0, encrypted with a fixed IV.
+    pub fn decrypt_legacy(&self, ciphertext: &[u8]) -> anyhow::Result<Vec<u8>> {
+        match self {
+            Crypter::Aes128Ctr(cipher) => Ok(apply_keystream(cipher, &LEGACY_IV, ciphertext)),
+            _ => Err(anyhow::Error::msg(
+                "Bundles of 0.4.0 are always encrypted with AES-128-CTR.",
+            )),
+        }
+    }
+}
+
+fn payload<'a>(msg: &'a [u8], aad: &'a [u8]) -> Payload<'a, 'a> {
+    Payload { msg, aad }
 }
 
-pub fn decrypt_ctr(cipher: &Aes128, ciphertext: &[u8]) -> Vec<u8> {
-    encrypt_ctr(cipher, ciphertext)
+fn apply_chacha20(key: &[u8; 32], nonce: &[u8], input: &[u8]) -> Vec<u8> {
+    let mut output = input.to_vec();
+    ChaCha20::new(key.into(), GenericArray::from_slice(nonce)).apply_keystream(&mut output);
+    output
 }
@@ -0,0 +1,95 @@
+use std::{
+    fmt, io,
+    path::{Path, PathBuf},
+};
+
+use bevy::asset::AssetIoError;
+
+/// Authenticated entry data did not match its tag or signed hash, so the bundle was modified or the key is wrong.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct IntegrityError;
+
+impl fmt::Display for IntegrityError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("integrity check failed, bundle entry was modified or the encryption key is wrong")
+    }
+}
+
+impl std::error::Error for IntegrityError {}
+
+/// Step of reading a bundle entry that failed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EntryStage {
+    /// Reading the raw entry bytes from the bundle file.
+    Read,
+    /// Checking the entry against its signed hash.
+    Verify,
+    /// Decrypting the entry.
+    Decrypt,
+    /// Decompressing the entry.
+    Inflate,
+}
+
+impl fmt::Display for EntryStage {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            EntryStage::Read => "read",
+            EntryStage::Verify => "verify",
+            EntryStage::Decrypt => "decrypt",
+            EntryStage::Inflate => "inflate",
+        })
+    }
+}
+
+/// Failure to load a single bundle entry.
+#[derive(Debug)]
+pub struct EntryError {
+    pub path: PathBuf,
+    pub stage: EntryStage,
+    source: anyhow::Error,
+}
+
+impl EntryError {
+    pub fn new(path: &Path, stage: EntryStage, source: anyhow::Error) -> Self {
+        Self {
+            path: path.to_path_buf(),
+            stage,
+            source,
+        }
+    }
+
+    /// Returns `true` if the entry failed authentication, see [`IntegrityError`].
+    pub fn is_integrity_failure(&self) -> bool {
+        self.source.is::<IntegrityError>()
+    }
+}
+
+impl fmt::Display for EntryError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "failed to {} bundle entry {}: {:#}",
+            self.stage,
+            self.path.display(),
+            self.source
+        )
+    }
+}
+
+/// The cause is already part of the message, so it is not returned as the source as well, which would print it twice
+/// in error chains.
+impl std::error::Error for EntryError {}
+
+impl From<EntryError> for AssetIoError {
+    fn from(err: EntryError) -> Self {
+        let kind = match err.stage {
+            EntryStage::Read => err
+                .source
+                .downcast_ref::<io::Error>()
+                .map(io::Error::kind)
+                .unwrap_or(io::ErrorKind::Other),
+            EntryStage::Verify | EntryStage::Decrypt | EntryStage::Inflate => io::ErrorKind::InvalidData,
+        };
+        AssetIoError::Io(io::Error::new(kind, err))
+    }
+}
@@ -0,0 +1,88 @@
+use std::{collections::HashMap, env, fmt::Debug};
+
+use crate::asset_bundling_options::{Cipher, EncryptionKey};
+
+/// Supplies encryption keys when a bundle is loaded, instead of baking them into [`AssetBundlingOptions`].
+///
+/// Set it with [`AssetBundlingOptions::set_key_provider`].
+///
+/// [`AssetBundlingOptions`]: crate::asset_bundling_options::AssetBundlingOptions
+/// [`AssetBundlingOptions::set_key_provider`]: crate::asset_bundling_options::AssetBundlingOptions::set_key_provider
+pub trait KeyProvider: Debug + Send + Sync {
+    /// Returns the key of the bundle named `bundle_id`, for the `cipher` recorded in that bundle.
+    fn key(&self, bundle_id: &str, cipher: Cipher) -> anyhow::Result<EncryptionKey>;
+
+    /// Environment variables the key of `bundle_id` is read from, so build scripts rerun when they change.
+    fn env_vars(&self, _bundle_id: &str) -> Vec<String> {
+        Vec::new()
+    }
+}
+
+/// Provides keys known at construction time, per bundle id.
+#[derive(Debug, Clone, Default)]
+pub struct StaticKeyProvider {
+    keys: HashMap<String, EncryptionKey>,
+}
+
+impl StaticKeyProvider {
+    pub fn with_key(mut self, bundle_id: impl Into<String>, key: impl Into<EncryptionKey>) -> Self {
+        self.keys.insert(bundle_id.into(), key.into());
+        self
+    }
+}
+
+impl KeyProvider for StaticKeyProvider {
+    fn key(&self, bundle_id: &str, _cipher: Cipher) -> anyhow::Result<EncryptionKey> {
+        self.keys
+            .get(bundle_id)
+            .cloned()
+            .ok_or_else(|| anyhow::Error::msg(format!("No encryption key for asset bundle {}.", bundle_id)))
+    }
+}
+
+/// Reads hex encoded keys from environment variables named after the bundle, see [`EnvKeyProvider::var_name`].
+#[derive(Debug, Clone)]
+pub struct EnvKeyProvider {
+    prefix: String,
+}
+
+impl Default for EnvKeyProvider {
+    fn default() -> Self {
+        Self::new("BEVY_ASSET_KEY_")
+    }
+}
+
+impl EnvKeyProvider {
+    pub fn new(prefix: impl Into<String>) -> Self {
+        Self { prefix: prefix.into() }
+    }
+
+    /// Name of the variable holding the key of `bundle_id`: the prefix followed by the bundle id in upper case, with
+    /// characters other than letters and digits replaced by `_`, like `BEVY_ASSET_KEY_ASSETS_BIN` for `assets.bin`.
+    pub fn var_name(&self, bundle_id: &str) -> String {
+        let suffix: String = bundle_id
+            .chars()
+            .map(|c| {
+                if c.is_ascii_alphanumeric() {
+                    c.to_ascii_uppercase()
+                } else {
+                    '_'
+                }
+            })
+            .collect();
+        format!("{}{}", self.prefix, suffix)
+    }
+}
+
+impl KeyProvider for EnvKeyProvider {
+    fn key(&self, bundle_id: &str, cipher: Cipher) -> anyhow::Result<EncryptionKey> {
+        let var = self.var_name(bundle_id);
+        let hex = env::var(&var)
+            .map_err(|err| anyhow::Error::msg(format!("Failed to read encryption key from {}: {}", var, err)))?;
+        EncryptionKey::from_hex(cipher, &hex)
+    }
+
+    fn env_vars(&self, bundle_id: &str) -> Vec<String> {
+        vec![self.var_name(bundle_id)]
+    }
+}
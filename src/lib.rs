@@ -22,15 +22,23 @@
 //! bevy_asset_packer = "0.4.0"
 //! ```
 //!
+//!The default `bundler` feature provides `AssetBundler` and the crates it needs to walk and filter the asset folder.
+//!Only build.rs packs bundles, so the game can leave it out:
+//!
+//!```toml
+//! [dependencies]
+//! bevy_asset_packer = { version = "0.4.0", default-features = false, features = ["deflate"] }
+//! ```
+//!
 //!### System setup
 //!
 //!In src/main.rs
 //!
-//!```rust
+//!```rust,ignore
 //! fn main() {
 //!    let mut options = AssetBundlingOptions::default();
 //!    options.encode_file_names = true;
-//!    options.compress_on = true;
+//!    options.compression = Compression::Deflate(9);
 //!    options.set_encryption_key([57, 206, 200, 7, 215, 17, 45, 219, 131, 171, 8, 214, 85, 12, 129, 176]);
 //!
 //!    App::new()
@@ -45,22 +53,243 @@
 //!
 //!In build.rs
 //!
-//!```rust
+//!```rust,ignore
 //! fn main() {
 //!    let mut options = AssetBundlingOptions::default();
 //!    options.encode_file_names = true;
-//!    options.compress_on = true;
+//!    options.compression = Compression::Deflate(9);
 //!    options.set_encryption_key([57, 206, 200, 7, 215, 17, 45, 219, 131, 171, 8, 214, 85, 12, 129, 176]);
 //!    AssetBundler::from(options).build().unwrap();
 //! }
 //! ```
 //!
+//!In build scripts the bundler prints `cargo:rerun-if-changed` for the asset folder and every packed file, so cargo
+//!only reruns build.rs when assets change. Use `with_rerun_if_changed(false)` to disable it.
+//!
+//!### Ciphers
+//!
+//!`set_encryption_key` takes a `[u8; 16]` AES-128 key, or an `EncryptionKey` to use AES-256 or ChaCha20. With
+//!`CipherMode::Gcm` entries are authenticated with AES-GCM or ChaCha20-Poly1305. The cipher is recorded in the bundle:
+//!
+//!```rust,ignore
+//! options.set_encryption_key(EncryptionKey::ChaCha20(key));
+//! options.with_cipher_mode(CipherMode::Gcm);
+//! ```
+//!
+//!### Passphrases
+//!
+//!Instead of a raw key, the key can be derived from a passphrase with Argon2, using a salt stored in the bundle. Read
+//!it from an environment variable in build.rs, so it stays out of source control:
+//!
+//!```rust,ignore
+//! options.set_encryption_passphrase_from_env("ASSET_PASSPHRASE").unwrap();
+//! ```
+//!
+//!And compile the same passphrase into the game in src/main.rs:
+//!
+//!```rust,ignore
+//! options.set_encryption_passphrase(env!("ASSET_PASSPHRASE"));
+//! ```
+//!
+//!### Key providers
+//!
+//!To fetch keys when a bundle is loaded, for example from a license file or after a DLC purchase, implement
+//!`KeyProvider` or use one of the bundled providers. They are asked for a key by bundle name:
+//!
+//!```rust,ignore
+//! options.set_key_provider(EnvKeyProvider::default()); // reads BEVY_ASSET_KEY_ASSETS_BIN
+//! options.set_key_provider(StaticKeyProvider::default().with_key("dlc.bin", key));
+//! ```
+//!
+//!### Hiding the key in the executable
+//!
+//!A key written as a byte array is easy to find in the executable. `obfuscated_key!` masks it at compile time and only
+//!reconstructs it when it is handed to the options. The hex key can be read from an environment variable while
+//!compiling, so it does not need to be in the source either:
+//!
+//!```rust,ignore
+//! const KEY: ObfuscatedKey<16> = obfuscated_key!(env "ASSET_KEY");
+//!
+//! options.set_encryption_key(KEY);
+//! // or for 256 bit keys
+//! options.set_encryption_key(EncryptionKey::Aes256(KEY_256.reveal()));
+//! ```
+//!
+//!This only keeps the key from being found by scanning the executable, it can still be read from memory while the game
+//!runs.
+//!
+//!### Signed bundles
+//!
+//!Encryption does not stop anyone from replacing entries with ones encrypted under the same key. Sign the bundle with an
+//!Ed25519 key, generated with `bevy_asset_packer keygen` or `bundle_signature::generate_signing_key`, and embed only the
+//!public key in the game. Unsigned bundles, bundles signed with another key and modified entries are then rejected:
+//!
+//!```rust,ignore
+//! // build.rs, with the signing key kept out of the repository
+//! AssetBundler::from(options)
+//!     .with_signing_key(signing_key)
+//!     .build()
+//!     .unwrap();
+//!
+//! // game
+//! options.set_verifying_key(VERIFYING_KEY);
+//! ```
+//!
+//!### Filtering assets
+//!
+//!Leave editor and source files out of the bundle with globs, or with `.assetignore` files using gitignore syntax in
+//!the asset folder or any folder below it:
+//!
+//!```rust,ignore
+//! AssetBundler::from(options)
+//!     .with_exclude("*.psd")
+//!     .with_exclude(".DS_Store")
+//!     .build()
+//!     .unwrap();
+//! ```
+//!
+//!### Choosing compression per file
+//!
+//!Already compressed files, such as png or ogg, gain little from compression. Store them as is, or let the bundler
+//!store any entry that compression does not shrink enough:
+//!
+//!```rust,ignore
+//! AssetBundler::from(options)
+//!     .with_compression_rule("*.png", Compression::Stored)
+//!     .with_compression_rule("*.ogg", Compression::Stored)
+//!     .with_min_compression_saving(0.05)
+//!     .build()
+//!     .unwrap();
+//! ```
+//!
+//!### Reproducible bundles
+//!
+//!Entries are written in sorted order without filesystem metadata, so unencrypted bundles of the same assets are
+//!byte-identical. Encrypted bundles use random nonces unless they are derived from the key and data:
+//!
+//!```rust,ignore
+//! options.deterministic_nonces = true;
+//! ```
+//!
+//!Keys derived from a passphrase use a random salt, so also give the bundler a fixed salt:
+//!
+//!```rust,ignore
+//! AssetBundler::from(options).with_key_salt(*b"my game salt 001").build().unwrap();
+//! ```
+//!
+//!### Incremental builds
+//!
+//!Keep encoded entries in a cache file, so build.rs only compresses and encrypts assets that changed:
+//!
+//!```rust,ignore
+//! AssetBundler::from(options)
+//!     .with_cache_path(PathBuf::from(env::var("OUT_DIR").unwrap()).join("assets.cache"))
+//!     .build()
+//!     .unwrap();
+//! ```
+//!
+//!### Embedding the bundle
+//!
+//!To ship a single executable, write the bundle to `OUT_DIR` in build.rs:
+//!
+//!```rust,ignore
+//! AssetBundler::from(options).with_out_dir_output().unwrap().build().unwrap();
+//! ```
+//!
+//!And read it from memory in src/main.rs:
+//!
+//!```rust,ignore
+//! BundledAssetIoPlugin::from_embedded(options, include_asset_bundle!())
+//! ```
+//!
+//!### Bundles from 0.4.0
+//!
+//!Bundles built by version 0.4.0 have no header and can still be loaded. Set the options they were packed with, using
+//!`Compression::Deflate(9)` for `compress_on = true` and the same AES-128 key.
+//!
+//!### Command-line packer
+//!
+//!Build the `bevy_asset_packer` binary with the `cli` feature to pack and inspect bundles without cargo:
+//!
+//!```sh
+//! cargo install bevy_asset_packer --features cli
+//! bevy_asset_packer pack assets -o assets.bin --compression deflate --store "*.png" --encode-file-names --key 39cec807d7112ddb83ab08d6550c81b0
+//! bevy_asset_packer list assets.bin --key 39cec807d7112ddb83ab08d6550c81b0
+//! bevy_asset_packer verify assets.bin --key 39cec807d7112ddb83ab08d6550c81b0
+//! bevy_asset_packer unpack assets.bin -o extracted --key 39cec807d7112ddb83ab08d6550c81b0
+//! ASSET_KEY=39cec807d7112ddb83ab08d6550c81b0 bevy_asset_packer verify assets.bin --key-env ASSET_KEY
+//! bevy_asset_packer keygen
+//! SIGNING_KEY=... bevy_asset_packer pack assets -o assets.bin --signing-key-env SIGNING_KEY
+//! bevy_asset_packer verify assets.bin --verifying-key <public key>
+//! ```
+//!
 //!You can see examle in example folder.  
 //!And its all!!!
 
+#[cfg(feature = "bundler")]
 pub mod asset_bundler;
 pub mod asset_bundling_options;
+pub mod asset_unbundler;
+#[cfg(feature = "bundler")]
+mod build_cache;
+mod bundle_header;
+pub mod bundle_location;
+pub mod bundle_reader;
+pub mod bundle_signature;
+mod bundle_source;
 pub mod bundled_asset_io;
 pub mod bundled_asset_plugin;
+pub mod compression;
 mod crypt;
+pub mod error;
+pub mod key_provider;
+mod mounted_bundle;
+pub mod obfuscated_key;
 mod path_info;
+
+/// Embeds a bundle written by [`AssetBundler::with_out_dir_output`](asset_bundler::AssetBundler::with_out_dir_output)
+/// into the executable as a `&'static [u8]`, defaulting to `assets.bin`.
+///
+/// ```rust,ignore
+/// BundledAssetIoPlugin::from_embedded(options, include_asset_bundle!())
+/// ```
+#[macro_export]
+macro_rules! include_asset_bundle {
+    () => {
+        $crate::include_asset_bundle!("assets.bin")
+    };
+    ($name:literal) => {
+        include_bytes!(concat!(env!("OUT_DIR"), "/", $name)) as &'static [u8]
+    };
+}
+
+/// Masks an encryption key at compile time into an [`ObfuscatedKey`](obfuscated_key::ObfuscatedKey), so the plain
+/// key bytes never end up in the executable. Takes a byte array, a hex string or `env "VAR"` to read the hex key from
+/// an environment variable while compiling.
+///
+/// ```rust,ignore
+/// const KEY: ObfuscatedKey<16> = obfuscated_key!(env "ASSET_KEY");
+/// options.set_encryption_key(KEY);
+/// ```
+#[macro_export]
+macro_rules! obfuscated_key {
+    (env $var:literal) => {
+        $crate::obfuscated_key!(env!($var))
+    };
+    ([$($byte:expr),* $(,)?]) => {
+        const {
+            $crate::obfuscated_key::ObfuscatedKey::new(
+                [$($byte),*],
+                $crate::obfuscated_key::seed(concat!(file!(), ":", line!(), ":", column!())),
+            )
+        }
+    };
+    ($hex:expr) => {
+        const {
+            $crate::obfuscated_key::ObfuscatedKey::from_hex(
+                $hex,
+                $crate::obfuscated_key::seed(concat!(file!(), ":", line!(), ":", column!())),
+            )
+        }
+    };
+}
@@ -0,0 +1,274 @@
+use std::{
+    borrow::Cow,
+    collections::HashMap,
+    io::Read,
+    path::{Path, PathBuf},
+    sync::{Arc, RwLock},
+};
+
+use bevy::asset::AssetIoError;
+use tar::Archive;
+
+use crate::{
+    asset_bundling_options::AssetBundlingOptions,
+    bundle_header::BundleHeader,
+    bundle_signature::{entry_hash, Manifest, MANIFEST_ENTRY_NAME},
+    bundle_source::{BundleSource, OffsetReader},
+    compression::Compression,
+    error::{EntryError, EntryStage, IntegrityError},
+    path_info::ArchivePathInfo,
+};
+
+type ParentDirToPathInfo = HashMap<String, Vec<ArchivePathInfo>>;
+
+/// Single bundle file mounted into [`BundledAssetIo`](crate::bundled_asset_io::BundledAssetIo).
+pub(crate) struct MountedBundle {
+    options: AssetBundlingOptions,
+    priority: i32,
+    embedded: Option<&'static [u8]>,
+    source: Option<BundleSource>,
+    header: Option<BundleHeader>,
+    parent_dir_to_path_info: Option<Arc<RwLock<ParentDirToPathInfo>>>,
+}
+
+impl MountedBundle {
+    pub fn new(options: AssetBundlingOptions, priority: i32) -> Self {
+        Self {
+            options,
+            priority,
+            embedded: None,
+            source: None,
+            header: None,
+            parent_dir_to_path_info: None,
+        }
+    }
+
+    /// Creates a bundle that is read from `bytes` instead of a file.
+    pub fn embedded(options: AssetBundlingOptions, bytes: &'static [u8], priority: i32) -> Self {
+        Self {
+            embedded: Some(bytes),
+            ..Self::new(options, priority)
+        }
+    }
+
+    pub fn options(&self) -> &AssetBundlingOptions {
+        &self.options
+    }
+
+    pub fn priority(&self) -> i32 {
+        self.priority
+    }
+
+    pub fn ensure_loaded(&mut self) -> anyhow::Result<()> {
+        if self.parent_dir_to_path_info.is_none() {
+            let source = match self.embedded {
+                Some(bytes) => BundleSource::Static(bytes),
+                #[cfg(feature = "mmap")]
+                None if self.options.memory_map => BundleSource::map(&self.options.find_bundle_path()?)?,
+                None => BundleSource::File(self.options.find_bundle_path()?),
+            };
+            let mut reader = source.open()?;
+            let header = BundleHeader::read(&mut reader, &self.options)?;
+            let options = if header.encrypted {
+                self.options.resolve_key(header.cipher, header.key_salt.as_ref())?
+            } else {
+                self.options.clone()
+            };
+            header.check(&options)?;
+            let reader = OffsetReader::new(reader)?;
+            let data_start = reader.start();
+            let bundle_size = source.size()?;
+            let mut archive = Archive::new(reader);
+            let mut infos = Vec::new();
+            let mut manifest_data = None;
+            // Seeking over entry bodies keeps startup from reading the whole bundle.
+            for entry in archive.entries_with_seek()? {
+                let mut entry = entry?;
+                let path = entry.path()?.into_owned();
+                let name_in_archive = normalize_path(&path);
+                if header.signed && name_in_archive == MANIFEST_ENTRY_NAME {
+                    if options.verifying_key.is_some() {
+                        let mut data = Vec::new();
+                        entry.read_to_end(&mut data)?;
+                        manifest_data = Some(data);
+                    }
+                    continue;
+                }
+                let decoded_path = if options.encode_file_names {
+                    options.try_decode_path_for(&header, &path)?
+                } else {
+                    path
+                };
+                let offset = data_start + entry.raw_file_position();
+                if offset.saturating_add(entry.size()) > bundle_size {
+                    return Err(anyhow::Error::msg(format!(
+                        "Asset bundle entry {} ends past the end of the bundle.",
+                        decoded_path.display()
+                    )));
+                }
+                let path_info = ArchivePathInfo::new(decoded_path, offset, entry.size());
+                infos.push((name_in_archive, path_info));
+            }
+            drop(archive);
+            if let Some(verifying_key) = &options.verifying_key {
+                let manifest_data =
+                    manifest_data.ok_or_else(|| anyhow::Error::msg("Asset bundle is signed but has no manifest."))?;
+                let mut manifest = Manifest::verify(verifying_key, &header.to_bytes()?, &manifest_data)?;
+                for (name_in_archive, path_info) in &mut infos {
+                    let hash = manifest.take(name_in_archive).ok_or_else(|| {
+                        anyhow::Error::msg(format!(
+                            "Asset bundle entry {} is not signed.",
+                            path_info.path().display()
+                        ))
+                    })?;
+                    path_info.set_hash(hash);
+                }
+                if !manifest.is_empty() {
+                    return Err(anyhow::Error::msg("Asset bundle is missing signed entries."));
+                }
+            }
+            let mut mappings: ParentDirToPathInfo = HashMap::new();
+            for (_, path_info) in infos {
+                let mut parent_dir = path_info.path();
+                let parent_dir_str = if parent_dir.pop() {
+                    normalize_path(&parent_dir)
+                } else {
+                    "".into()
+                };
+                mappings.entry(parent_dir_str).or_default().push(path_info);
+            }
+            self.options = options;
+            self.source = Some(source);
+            self.header = Some(header);
+            self.parent_dir_to_path_info = Some(Arc::new(RwLock::new(mappings)));
+            Ok(())
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Returns the path and stored size of every entry, in bundle order.
+    pub fn entries(&self) -> Vec<(PathBuf, u64)> {
+        let Some(lock) = &self.parent_dir_to_path_info else {
+            return Vec::new();
+        };
+        let mappings = lock.read().unwrap();
+        let mut infos: Vec<_> = mappings.values().flatten().collect();
+        infos.sort_by_key(|info| info.offset());
+        infos.into_iter().map(|info| (info.path(), info.size())).collect()
+    }
+
+    /// Returns the offset, stored size and signed hash of the entry at `path`.
+    fn find_entry(&self, path: &Path) -> Option<(u64, u64, Option<[u8; 32]>)> {
+        let lock = self.parent_dir_to_path_info.as_ref()?;
+        let mappings = lock.read().unwrap();
+        let mut parent_dir = path.to_path_buf();
+        let parent_dir_str = if parent_dir.pop() {
+            normalize_path(&parent_dir)
+        } else {
+            "".into()
+        };
+        mappings
+            .get(&parent_dir_str)?
+            .iter()
+            .find(|info| info.path() == path)
+            .map(|info| (info.offset(), info.size(), info.hash()))
+    }
+
+    fn read_entry_data(&self, offset: u64, size: u64) -> anyhow::Result<Cow<'_, [u8]>> {
+        self.source
+            .as_ref()
+            .ok_or_else(|| anyhow::Error::msg("Asset bundle is not loaded."))?
+            .read_range(offset, size)
+    }
+
+    pub fn load(&self, path: &Path) -> Result<Vec<u8>, AssetIoError> {
+        match self.load_entry(path) {
+            Some(result) => result.map_err(AssetIoError::from),
+            None => Err(AssetIoError::NotFound(path.to_path_buf())),
+        }
+    }
+
+    /// Reads and decodes the entry at `path`, returns `None` if the bundle has no such entry.
+    pub fn load_entry(&self, path: &Path) -> Option<Result<Vec<u8>, EntryError>> {
+        let (offset, size, hash) = self.find_entry(path)?;
+        let header = self.header.as_ref()?;
+        Some(self.decode_entry(header, path, offset, size, hash))
+    }
+
+    fn decode_entry(
+        &self,
+        header: &BundleHeader,
+        path: &Path,
+        offset: u64,
+        size: u64,
+        hash: Option<[u8; 32]>,
+    ) -> Result<Vec<u8>, EntryError> {
+        let raw = self
+            .read_entry_data(offset, size)
+            .map_err(|err| EntryError::new(path, EntryStage::Read, err))?;
+        if hash.is_some_and(|hash| entry_hash(&raw) != hash) {
+            return Err(EntryError::new(path, EntryStage::Verify, IntegrityError.into()));
+        }
+
+        let (compression, payload) = if header.is_unversioned() {
+            (header.legacy_compression(), &raw[..])
+        } else {
+            let (id, payload) = raw
+                .split_first()
+                .ok_or_else(|| EntryError::new(path, EntryStage::Read, anyhow::Error::msg("Bundle entry is empty.")))?;
+            let compression =
+                Compression::from_id(*id).map_err(|err| EntryError::new(path, EntryStage::Inflate, err))?;
+            (compression, payload)
+        };
+
+        let data = match self.options.try_decrypt_entry_for(header, path, compression, payload) {
+            Ok(Some(decrypted)) => Cow::Owned(decrypted),
+            Ok(None) => Cow::Borrowed(payload),
+            Err(err) => return Err(EntryError::new(path, EntryStage::Decrypt, err)),
+        };
+        if compression == Compression::Stored {
+            return Ok(data.into_owned());
+        }
+        compression
+            .decompress(&data)
+            .map_err(|err| EntryError::new(path, EntryStage::Inflate, err))
+    }
+
+    pub fn read_directory(&self, path: &Path) -> Result<Vec<PathBuf>, AssetIoError> {
+        if let Some(lock) = self.parent_dir_to_path_info.clone() {
+            let mappings = lock.read().unwrap();
+            let path_str = normalize_path(path);
+            if let Some(entries) = mappings.get(&path_str) {
+                let vec: Vec<_> = entries.iter().map(|e| e.path()).collect();
+                return Ok(vec);
+            }
+        }
+        Err(AssetIoError::NotFound(path.to_path_buf()))
+    }
+
+    pub fn get_metadata(&self, path: &Path) -> Result<bevy::asset::Metadata, AssetIoError> {
+        if let Some(lock) = self.parent_dir_to_path_info.clone() {
+            let mappings = lock.read().unwrap();
+            let path_str = normalize_path(path);
+            if mappings.contains_key(&path_str) {
+                Ok(bevy::asset::Metadata::new(bevy::asset::FileType::Directory))
+            } else {
+                for v in mappings.values() {
+                    for info in v {
+                        if info.path() == path {
+                            return Ok(bevy::asset::Metadata::new(bevy::asset::FileType::File));
+                        }
+                    }
+                }
+                Err(AssetIoError::NotFound(path.to_path_buf()))
+            }
+        } else {
+            Err(AssetIoError::NotFound(path.to_path_buf()))
+        }
+    }
+}
+
+pub(crate) fn normalize_path(path: &Path) -> String {
+    path.to_str().unwrap_or("").replace('\\', "/")
+}
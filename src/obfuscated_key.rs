@@ -0,0 +1,131 @@
+use std::hint::black_box;
+
+use crate::asset_bundling_options::EncryptionKey;
+
+/// Key stored XOR masked in the binary, so it can not be found by scanning for its bytes. Create it with
+/// [`obfuscated_key!`](macro@crate::obfuscated_key), which masks the key at compile time.
+///
+/// Masking only hides the key from casual inspection, anyone stepping through the game can still read it once it is
+/// revealed.
+#[derive(Clone, Copy)]
+pub struct ObfuscatedKey<const N: usize> {
+    masked: [u8; N],
+    seed: u64,
+}
+
+impl<const N: usize> ObfuscatedKey<N> {
+    /// Masks `key` with bytes generated from `seed`. Call it in a const context, otherwise the plain key is in the
+    /// binary anyway.
+    pub const fn new(key: [u8; N], seed: u64) -> Self {
+        let mask = mask::<N>(seed);
+        let mut masked = [0; N];
+        let mut i = 0;
+        while i < N {
+            masked[i] = key[i] ^ mask[i];
+            i += 1;
+        }
+        Self { masked, seed }
+    }
+
+    /// Same as [`Self::new`], with the key written as `2 * N` hex digits. Fails to compile on invalid digits.
+    pub const fn from_hex(hex: &str, seed: u64) -> Self {
+        let hex = hex.as_bytes();
+        assert!(hex.len() == 2 * N, "Obfuscated key has the wrong number of hex digits.");
+        let mut key = [0; N];
+        let mut i = 0;
+        while i < N {
+            key[i] = hex_digit(hex[2 * i]) << 4 | hex_digit(hex[2 * i + 1]);
+            i += 1;
+        }
+        Self::new(key, seed)
+    }
+
+    /// Reconstructs the key.
+    pub fn reveal(&self) -> [u8; N] {
+        // Keeps the optimizer from folding the unmasking into a constant holding the plain key.
+        let masked = black_box(self.masked);
+        let mask = mask::<N>(black_box(self.seed));
+        let mut key = [0; N];
+        for i in 0..N {
+            key[i] = masked[i] ^ mask[i];
+        }
+        key
+    }
+}
+
+impl From<ObfuscatedKey<16>> for EncryptionKey {
+    fn from(key: ObfuscatedKey<16>) -> Self {
+        EncryptionKey::Aes128(key.reveal())
+    }
+}
+
+/// Derives a masking seed from `location`, so every key gets a different mask.
+#[doc(hidden)]
+pub const fn seed(location: &str) -> u64 {
+    let bytes = location.as_bytes();
+    let mut hash = 0xcbf2_9ce4_8422_2325_u64;
+    let mut i = 0;
+    while i < bytes.len() {
+        hash ^= bytes[i] as u64;
+        hash = hash.wrapping_mul(0x0100_0000_01b3);
+        i += 1;
+    }
+    hash
+}
+
+/// Generates `N` mask bytes from `seed` with xorshift64*.
+const fn mask<const N: usize>(seed: u64) -> [u8; N] {
+    let mut state = seed | 1;
+    let mut mask = [0; N];
+    let mut i = 0;
+    while i < N {
+        state ^= state >> 12;
+        state ^= state << 25;
+        state ^= state >> 27;
+        mask[i] = (state.wrapping_mul(0x2545_f491_4f6c_dd1d) >> 56) as u8;
+        i += 1;
+    }
+    mask
+}
+
+const fn hex_digit(digit: u8) -> u8 {
+    match digit {
+        b'0'..=b'9' => digit - b'0',
+        b'a'..=b'f' => digit - b'a' + 10,
+        b'A'..=b'F' => digit - b'A' + 10,
+        _ => panic!("Obfuscated key has an invalid hex digit."),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const KEY: [u8; 16] = [0, 1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 14, 15];
+
+    #[test]
+    fn reveals_key_of_every_form() {
+        let from_array: ObfuscatedKey<16> =
+            crate::obfuscated_key!([0, 1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 14, 15]);
+        let from_hex: ObfuscatedKey<16> = crate::obfuscated_key!("000102030405060708090A0b0c0d0e0f");
+        let from_env: ObfuscatedKey<16> = crate::obfuscated_key!(env "OBFUSCATED_KEY_TEST_KEY");
+        for key in [from_array, from_hex, from_env] {
+            assert_eq!(key.reveal(), KEY);
+            assert_ne!(key.masked, KEY);
+        }
+    }
+
+    #[test]
+    fn masks_every_key_differently() {
+        let first: ObfuscatedKey<16> = crate::obfuscated_key!([0, 1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 14, 15]);
+        let second: ObfuscatedKey<16> = crate::obfuscated_key!([0, 1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 14, 15]);
+        assert_ne!(first.masked, second.masked);
+        assert_eq!(first.reveal(), second.reveal());
+    }
+
+    #[test]
+    fn converts_into_aes_128_key() {
+        let key: EncryptionKey = crate::obfuscated_key!("000102030405060708090a0b0c0d0e0f").into();
+        assert!(matches!(key, EncryptionKey::Aes128(bytes) if bytes == KEY));
+    }
+}
@@ -2,14 +2,41 @@ use std::path::PathBuf;
 
 pub(crate) struct ArchivePathInfo {
     path: PathBuf,
+    offset: u64,
+    size: u64,
+    hash: Option<[u8; 32]>,
 }
 
 impl ArchivePathInfo {
-    pub fn new(path: PathBuf) -> Self {
-        Self { path }
+    pub fn new(path: PathBuf, offset: u64, size: u64) -> Self {
+        Self {
+            path,
+            offset,
+            size,
+            hash: None,
+        }
     }
 
     pub fn path(&self) -> PathBuf {
         self.path.clone()
     }
+
+    /// Byte offset of the entry data from the start of the bundle file.
+    pub fn offset(&self) -> u64 {
+        self.offset
+    }
+
+    /// Size of the entry data as stored in the bundle.
+    pub fn size(&self) -> u64 {
+        self.size
+    }
+
+    /// Signed hash of the stored entry data, if the bundle signature was checked.
+    pub fn hash(&self) -> Option<[u8; 32]> {
+        self.hash
+    }
+
+    pub fn set_hash(&mut self, hash: [u8; 32]) {
+        self.hash = Some(hash);
+    }
 }
@@ -0,0 +1,148 @@
+//! Incremental builds reuse encoded entries of unchanged files and never let a stale or broken cache break the bundle.
+
+#![cfg(feature = "bundler")]
+
+mod common;
+
+use std::{
+    fs,
+    io::Read,
+    path::{Path, PathBuf},
+};
+
+use bevy_asset_packer::{
+    asset_bundler::AssetBundler,
+    asset_bundling_options::{AssetBundlingOptions, CipherMode, EncryptionKey},
+    asset_unbundler::AssetUnbundler,
+};
+use common::{read_tree, write_assets, TempDir};
+
+/// Magic, format version, flags, cipher mode and cipher of a bundle without key salt.
+const HEADER_LEN: usize = 9;
+
+struct Build {
+    dir: TempDir,
+}
+
+impl Build {
+    fn new(name: &str) -> Self {
+        let dir = TempDir::new(name);
+        write_assets(&dir.path().join("assets"));
+        Self { dir }
+    }
+
+    fn assets(&self) -> PathBuf {
+        self.dir.path().join("assets")
+    }
+
+    fn cache(&self) -> PathBuf {
+        self.dir.path().join("assets.cache")
+    }
+
+    /// Packs the assets with the cache and returns the bundle bytes.
+    fn pack(&self, options: &AssetBundlingOptions) -> Vec<u8> {
+        let bundle = self.dir.path().join("assets.bin");
+        AssetBundler::from(options.clone())
+            .with_asset_folder(self.assets().to_string_lossy())
+            .with_output_path(&bundle)
+            .with_rerun_if_changed(false)
+            .with_cache_path(self.cache())
+            .build()
+            .unwrap();
+        fs::read(bundle).unwrap()
+    }
+
+    /// Packs the assets with the cache and checks that the bundle extracts to them.
+    fn pack_and_check(&self, options: &AssetBundlingOptions) -> Vec<u8> {
+        let bytes = self.pack(options);
+        let extracted = self.dir.path().join("extracted");
+        let _ = fs::remove_dir_all(&extracted);
+        AssetUnbundler::from(options.clone())
+            .with_bundle_path(self.dir.path().join("assets.bin"))
+            .with_output_folder(&extracted)
+            .extract()
+            .unwrap();
+        assert!(read_tree(&self.assets()) == read_tree(&extracted));
+        bytes
+    }
+}
+
+fn encrypted_options(key: [u8; 16]) -> AssetBundlingOptions {
+    let mut options = AssetBundlingOptions::default();
+    options.set_encryption_key(EncryptionKey::Aes128(key));
+    options
+}
+
+/// Stored data of the entry named `name`.
+fn entry_data(bundle: &[u8], name: &str) -> Vec<u8> {
+    let mut archive = tar::Archive::new(&bundle[HEADER_LEN..]);
+    for entry in archive.entries().unwrap() {
+        let mut entry = entry.unwrap();
+        if entry.path().unwrap() == Path::new(name) {
+            let mut data = Vec::new();
+            entry.read_to_end(&mut data).unwrap();
+            return data;
+        }
+    }
+    panic!("bundle has no entry {}", name);
+}
+
+#[test]
+fn reuses_entries_of_unchanged_files() {
+    let build = Build::new("cache_reuse");
+    let options = encrypted_options([7; 16]);
+    let first = build.pack_and_check(&options);
+    // Nonces are random, so only reused entries encrypt to the same bytes.
+    assert_eq!(first, build.pack_and_check(&options));
+
+    fs::write(build.assets().join("notes.txt"), "changed notes").unwrap();
+    let changed = build.pack_and_check(&options);
+    assert_eq!(
+        entry_data(&first, "images/logo.png"),
+        entry_data(&changed, "images/logo.png")
+    );
+    assert_ne!(entry_data(&first, "notes.txt"), entry_data(&changed, "notes.txt"));
+}
+
+#[test]
+fn drops_entries_when_the_key_changes() {
+    let build = Build::new("cache_key");
+    let first = build.pack_and_check(&encrypted_options([7; 16]));
+    let second = build.pack_and_check(&encrypted_options([8; 16]));
+    assert_ne!(entry_data(&first, "notes.txt"), entry_data(&second, "notes.txt"));
+}
+
+#[test]
+fn drops_entries_when_options_change() {
+    let build = Build::new("cache_options");
+    let mut options = encrypted_options([7; 16]);
+    let first = build.pack_and_check(&options);
+    options.with_cipher_mode(CipherMode::Gcm);
+    let second = build.pack_and_check(&options);
+    assert_ne!(entry_data(&first, "notes.txt"), entry_data(&second, "notes.txt"));
+}
+
+#[test]
+fn recovers_from_truncated_cache() {
+    let build = Build::new("cache_truncated");
+    let options = encrypted_options([7; 16]);
+    build.pack_and_check(&options);
+    let cache = fs::read(build.cache()).unwrap();
+    for len in [0, 10, 80, cache.len() / 2, cache.len() - 1] {
+        fs::write(build.cache(), &cache[..len]).unwrap();
+        build.pack_and_check(&options);
+    }
+}
+
+#[test]
+fn recovers_from_corrupt_cache() {
+    let build = Build::new("cache_corrupt");
+    let options = encrypted_options([7; 16]);
+    build.pack_and_check(&options);
+    let mut cache = fs::read(build.cache()).unwrap();
+    for byte in &mut cache[120..] {
+        *byte = 0xff;
+    }
+    fs::write(build.cache(), cache).unwrap();
+    build.pack_and_check(&options);
+}
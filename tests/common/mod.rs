@@ -0,0 +1,93 @@
+#![allow(dead_code)]
+
+use std::{
+    collections::BTreeMap,
+    env, fs,
+    path::{Path, PathBuf},
+    process,
+    sync::atomic::{AtomicUsize, Ordering},
+};
+
+#[cfg(feature = "bundler")]
+use bevy_asset_packer::{asset_bundler::AssetBundler, asset_bundling_options::AssetBundlingOptions};
+
+/// Directory under the system temp dir, removed when dropped.
+pub struct TempDir(PathBuf);
+
+impl TempDir {
+    pub fn new(name: &str) -> Self {
+        static COUNT: AtomicUsize = AtomicUsize::new(0);
+        let path = env::temp_dir().join(format!(
+            "bevy_asset_packer_{}_{}_{}",
+            name,
+            process::id(),
+            COUNT.fetch_add(1, Ordering::Relaxed)
+        ));
+        let _ = fs::remove_dir_all(&path);
+        fs::create_dir_all(&path).unwrap();
+        Self(path)
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.0
+    }
+}
+
+impl Drop for TempDir {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.0);
+    }
+}
+
+/// Writes a small asset folder with compressible, incompressible, empty and nested files.
+pub fn write_assets(dir: &Path) {
+    let notes = "the quick brown fox jumps over the lazy dog\n".repeat(200);
+    let mut state = 0x2545_f491_4f6c_dd1d_u64;
+    let noise: Vec<u8> = (0..4000)
+        .map(|_| {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            state as u8
+        })
+        .collect();
+    let files: [(&str, &[u8]); 4] = [
+        ("notes.txt", notes.as_bytes()),
+        ("images/logo.png", &noise),
+        ("levels/deep/empty.txt", b""),
+        ("levels/d\u{e9}j\u{e0} vu.txt", b"d\xc3\xa9j\xc3\xa0 vu"),
+    ];
+    for (path, data) in files {
+        let path = dir.join(path);
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(path, data).unwrap();
+    }
+}
+
+/// Packs `asset_dir` into `bundle` with `options`.
+#[cfg(feature = "bundler")]
+pub fn pack(options: AssetBundlingOptions, asset_dir: &Path, bundle: &Path) -> anyhow::Result<()> {
+    let mut bundler = AssetBundler::from(options);
+    bundler
+        .with_asset_folder(asset_dir.to_string_lossy())
+        .with_output_path(bundle)
+        .with_rerun_if_changed(false);
+    bundler.build()
+}
+
+/// Contents of every file below `dir`, keyed by their path relative to it.
+pub fn read_tree(dir: &Path) -> BTreeMap<PathBuf, Vec<u8>> {
+    fn visit(dir: &Path, prefix: &Path, files: &mut BTreeMap<PathBuf, Vec<u8>>) {
+        for entry in fs::read_dir(dir).unwrap() {
+            let path = entry.unwrap().path();
+            if path.is_dir() {
+                visit(&path, prefix, files);
+            } else {
+                files.insert(path.strip_prefix(prefix).unwrap().to_owned(), fs::read(&path).unwrap());
+            }
+        }
+    }
+    let mut files = BTreeMap::new();
+    visit(dir, dir, &mut files);
+    files
+}
@@ -0,0 +1,133 @@
+//! Include and exclude globs and `.assetignore` files pick the files that are packed.
+
+#![cfg(feature = "bundler")]
+
+mod common;
+
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+use bevy_asset_packer::{
+    asset_bundler::{AssetBundler, ASSET_IGNORE_FILE_NAME},
+    asset_bundling_options::AssetBundlingOptions,
+    bundle_location::BundleLocation,
+    bundle_reader::BundleReader,
+};
+use common::TempDir;
+
+const FILES: [&str; 7] = [
+    "notes.txt",
+    "images/logo.png",
+    "images/.DS_Store",
+    "raw/logo.psd",
+    "raw/readme.txt",
+    "levels/one.txt",
+    "levels/deep/two.txt",
+];
+
+fn write_files(dir: &Path, files: &[&str]) {
+    for file in files {
+        let path = dir.join(file);
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(path, file.as_bytes()).unwrap();
+    }
+}
+
+/// Packs the asset folder of `dir` with `configure` applied and returns the sorted paths in the bundle.
+fn packed_paths(dir: &TempDir, configure: impl FnOnce(&mut AssetBundler)) -> Vec<PathBuf> {
+    let bundle = dir.path().join("assets.bin");
+    let mut bundler = AssetBundler::default();
+    bundler
+        .with_asset_folder(dir.path().join("assets").to_string_lossy())
+        .with_output_path(&bundle)
+        .with_rerun_if_changed(false);
+    configure(&mut bundler);
+    bundler.build().unwrap();
+
+    let mut options = AssetBundlingOptions::default();
+    options.set_search_paths([BundleLocation::Absolute(bundle)]);
+    let mut paths: Vec<_> = BundleReader::open(options)
+        .unwrap()
+        .entries()
+        .into_iter()
+        .map(|entry| entry.path)
+        .collect();
+    paths.sort();
+    paths
+}
+
+fn paths(paths: &[&str]) -> Vec<PathBuf> {
+    let mut paths: Vec<_> = paths.iter().map(PathBuf::from).collect();
+    paths.sort();
+    paths
+}
+
+#[test]
+fn packs_every_file_without_filters() {
+    let dir = TempDir::new("filter_none");
+    write_files(&dir.path().join("assets"), &FILES);
+    assert_eq!(packed_paths(&dir, |_| {}), paths(&FILES));
+}
+
+#[test]
+fn packs_only_included_files() {
+    let dir = TempDir::new("filter_include");
+    write_files(&dir.path().join("assets"), &FILES);
+    let packed = packed_paths(&dir, |bundler| {
+        bundler.with_include("*.png").with_include("levels/**");
+    });
+    assert_eq!(
+        packed,
+        paths(&["images/logo.png", "levels/one.txt", "levels/deep/two.txt"])
+    );
+}
+
+#[test]
+fn leaves_out_excluded_files() {
+    let dir = TempDir::new("filter_exclude");
+    write_files(&dir.path().join("assets"), &FILES);
+    let packed = packed_paths(&dir, |bundler| {
+        bundler.with_exclude(".DS_Store").with_exclude("raw/**");
+    });
+    assert_eq!(
+        packed,
+        paths(&["notes.txt", "images/logo.png", "levels/one.txt", "levels/deep/two.txt"])
+    );
+}
+
+#[test]
+fn exclude_wins_over_include() {
+    let dir = TempDir::new("filter_include_exclude");
+    write_files(&dir.path().join("assets"), &FILES);
+    let packed = packed_paths(&dir, |bundler| {
+        bundler
+            .with_include("*.txt")
+            .with_exclude("deep/**")
+            .with_exclude("/raw/*");
+    });
+    assert_eq!(packed, paths(&["notes.txt", "levels/one.txt", "levels/deep/two.txt"]));
+}
+
+#[test]
+fn honors_nested_asset_ignore_files() {
+    let dir = TempDir::new("filter_asset_ignore");
+    let assets = dir.path().join("assets");
+    write_files(&assets, &FILES);
+    write_files(&assets, &["levels/draft.tmp", "levels/keep.tmp", "raw/deep/draft.tmp"]);
+    fs::write(assets.join(ASSET_IGNORE_FILE_NAME), "*.tmp\n.DS_Store\n").unwrap();
+    fs::write(assets.join("raw").join(ASSET_IGNORE_FILE_NAME), "*.psd\n").unwrap();
+    fs::write(assets.join("levels").join(ASSET_IGNORE_FILE_NAME), "!keep.tmp\ndeep/\n").unwrap();
+
+    assert_eq!(
+        packed_paths(&dir, |_| {}),
+        paths(&[
+            "notes.txt",
+            "images/logo.png",
+            "raw/readme.txt",
+            "levels/one.txt",
+            "levels/keep.tmp",
+        ])
+    );
+}
@@ -0,0 +1,49 @@
+//! Bundles written by version 0.4.0, before bundles had a header.
+
+use std::{fs, path::Path};
+
+use bevy_asset_packer::{
+    asset_bundling_options::AssetBundlingOptions, bundle_location::BundleLocation, bundle_reader::BundleReader,
+};
+
+const FIXTURES: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/0.4.0");
+
+fn open(bundle_name: &str, mut options: AssetBundlingOptions) -> BundleReader {
+    options.set_search_paths([BundleLocation::Absolute(Path::new(FIXTURES).join(bundle_name))]);
+    BundleReader::open(options).unwrap()
+}
+
+fn assert_matches_assets(reader: &BundleReader) {
+    let mut paths: Vec<_> = reader.entries().into_iter().map(|entry| entry.path).collect();
+    paths.sort();
+    assert_eq!(paths, [Path::new("images/logo.png"), Path::new("notes.txt")]);
+    for path in paths {
+        let original = fs::read(Path::new(FIXTURES).join("assets").join(&path)).unwrap();
+        assert_eq!(reader.read(&path).unwrap(), original, "{}", path.display());
+    }
+}
+
+#[test]
+fn reads_plain_bundle() {
+    assert_matches_assets(&open("plain.bin", AssetBundlingOptions::default()));
+}
+
+#[cfg(feature = "deflate")]
+#[test]
+fn reads_encoded_compressed_encrypted_bundle() {
+    let mut options = AssetBundlingOptions {
+        encode_file_names: true,
+        compression: bevy_asset_packer::compression::Compression::Deflate(9),
+        ..AssetBundlingOptions::default()
+    };
+    options.set_encryption_key([57, 206, 200, 7, 215, 17, 45, 219, 131, 171, 8, 214, 85, 12, 129, 176]);
+    assert_matches_assets(&open("encoded_compressed_encrypted.bin", options));
+}
+
+#[test]
+fn rejects_unsigned_bundle_when_signature_is_required() {
+    let mut options = AssetBundlingOptions::default();
+    options.set_verifying_key([0; 32]);
+    options.set_search_paths([BundleLocation::Absolute(Path::new(FIXTURES).join("plain.bin"))]);
+    assert!(BundleReader::open(options).is_err());
+}
@@ -0,0 +1,38 @@
+//! Packing the same assets twice gives identical bundles when nonces are derived and the key salt is fixed.
+
+#![cfg(feature = "bundler")]
+
+mod common;
+
+use std::fs;
+
+use bevy_asset_packer::{asset_bundler::AssetBundler, asset_bundling_options::AssetBundlingOptions};
+use common::{write_assets, TempDir};
+
+fn pack_with_salt(dir: &TempDir, name: &str, salt: [u8; 16]) -> Vec<u8> {
+    let assets = dir.path().join("assets");
+    let bundle = dir.path().join(name);
+    let mut options = AssetBundlingOptions {
+        deterministic_nonces: true,
+        ..AssetBundlingOptions::default()
+    };
+    options.set_encryption_passphrase("correct horse battery staple");
+    AssetBundler::from(options)
+        .with_asset_folder(assets.to_string_lossy())
+        .with_output_path(&bundle)
+        .with_rerun_if_changed(false)
+        .with_key_salt(salt)
+        .build()
+        .unwrap();
+    fs::read(bundle).unwrap()
+}
+
+#[test]
+fn passphrase_bundles_with_fixed_salt_are_identical() {
+    let dir = TempDir::new("reproducible");
+    write_assets(&dir.path().join("assets"));
+
+    let first = pack_with_salt(&dir, "first.bin", [1; 16]);
+    assert_eq!(first, pack_with_salt(&dir, "second.bin", [1; 16]));
+    assert_ne!(first, pack_with_salt(&dir, "other_salt.bin", [2; 16]));
+}
@@ -0,0 +1,110 @@
+//! Packs an asset folder and extracts it again with every supported bundle format.
+
+#![cfg(feature = "bundler")]
+
+mod common;
+
+use bevy_asset_packer::{
+    asset_bundling_options::{AssetBundlingOptions, Cipher, CipherMode, EncryptionKey},
+    asset_unbundler::AssetUnbundler,
+    bundle_location::BundleLocation,
+    bundle_reader::BundleReader,
+    compression::Compression,
+};
+use common::{pack, read_tree, write_assets, TempDir};
+
+fn compressions() -> Vec<Compression> {
+    vec![
+        Compression::Stored,
+        #[cfg(feature = "deflate")]
+        Compression::Deflate(9),
+        #[cfg(feature = "zstd")]
+        Compression::Zstd(3),
+        #[cfg(feature = "lz4")]
+        Compression::Lz4,
+    ]
+}
+
+fn keys() -> [Option<EncryptionKey>; 4] {
+    [
+        None,
+        Some(EncryptionKey::Aes128([7; 16])),
+        Some(EncryptionKey::Aes256([8; 32])),
+        Some(EncryptionKey::ChaCha20([9; 32])),
+    ]
+}
+
+/// Packs and extracts the test assets with `options`, checking that every file comes back unchanged.
+fn assert_round_trip(options: AssetBundlingOptions) {
+    let dir = TempDir::new("round_trip");
+    let assets = dir.path().join("assets");
+    let bundle = dir.path().join("assets.bin");
+    let extracted = dir.path().join("extracted");
+    write_assets(&assets);
+
+    pack(options.clone(), &assets, &bundle).unwrap_or_else(|err| panic!("packing with {:?}: {:#}", options, err));
+    AssetUnbundler::from(options.clone())
+        .with_bundle_path(&bundle)
+        .with_output_folder(&extracted)
+        .extract()
+        .unwrap_or_else(|err| panic!("extracting with {:?}: {:#}", options, err));
+    assert!(
+        read_tree(&assets) == read_tree(&extracted),
+        "round trip with {:?}",
+        options
+    );
+}
+
+#[test]
+fn round_trips_every_format() {
+    for compression in compressions() {
+        for key in keys() {
+            for cipher_mode in [CipherMode::Ctr, CipherMode::Gcm] {
+                for encode_file_names in [false, true] {
+                    let mut options = AssetBundlingOptions {
+                        compression,
+                        encode_file_names,
+                        ..AssetBundlingOptions::default()
+                    };
+                    options.with_cipher_mode(cipher_mode);
+                    if let Some(key) = key.clone() {
+                        options.set_encryption_key(key);
+                    }
+                    assert_round_trip(options);
+                }
+            }
+        }
+    }
+}
+
+#[test]
+fn round_trips_passphrase_bundles() {
+    for cipher_mode in [CipherMode::Ctr, CipherMode::Gcm] {
+        let mut options = AssetBundlingOptions {
+            encode_file_names: true,
+            ..AssetBundlingOptions::default()
+        };
+        options
+            .with_cipher_mode(cipher_mode)
+            .set_encryption_passphrase("correct horse battery staple");
+        assert_round_trip(options);
+    }
+}
+
+#[test]
+fn rejects_key_of_another_cipher() {
+    let dir = TempDir::new("key_cipher");
+    let assets = dir.path().join("assets");
+    let bundle = dir.path().join("assets.bin");
+    write_assets(&assets);
+    let mut options = AssetBundlingOptions::default();
+    options.set_encryption_key(EncryptionKey::ChaCha20([9; 32]));
+    pack(options, &assets, &bundle).unwrap();
+    assert_eq!(BundleReader::cipher(&bundle).unwrap(), Some(Cipher::ChaCha20));
+
+    let mut options = AssetBundlingOptions::default();
+    options
+        .set_encryption_key(EncryptionKey::Aes256([9; 32]))
+        .set_search_paths([BundleLocation::Absolute(bundle)]);
+    assert!(BundleReader::open(options).is_err());
+}
@@ -0,0 +1,164 @@
+//! Signed bundles only load with the matching verifying key and when every entry is signed and unmodified.
+
+#![cfg(feature = "bundler")]
+
+mod common;
+
+use std::{
+    fs,
+    io::Read,
+    path::{Path, PathBuf},
+};
+
+use bevy_asset_packer::{
+    asset_bundler::AssetBundler,
+    asset_bundling_options::AssetBundlingOptions,
+    bundle_location::BundleLocation,
+    bundle_reader::BundleReader,
+    bundle_signature::verifying_key,
+    error::{EntryError, EntryStage},
+};
+use common::{read_tree, write_assets, TempDir};
+
+/// Magic, format version, flags, cipher mode and cipher of a bundle without key salt.
+const HEADER_LEN: usize = 9;
+const SIGNING_KEY: [u8; 32] = [3; 32];
+const MANIFEST: &str = ".bundle-manifest";
+
+/// Packs the test assets signed with `signing_key` and returns the asset folder and the bundle path.
+fn pack_signed(dir: &TempDir, signing_key: [u8; 32]) -> (PathBuf, PathBuf) {
+    let assets = dir.path().join("assets");
+    let bundle = dir.path().join("assets.bin");
+    write_assets(&assets);
+    AssetBundler::from(AssetBundlingOptions::default())
+        .with_asset_folder(assets.to_string_lossy())
+        .with_output_path(&bundle)
+        .with_rerun_if_changed(false)
+        .with_signing_key(signing_key)
+        .build()
+        .unwrap();
+    (assets, bundle)
+}
+
+fn open(bundle: &Path) -> anyhow::Result<BundleReader> {
+    let mut options = AssetBundlingOptions::default();
+    options
+        .set_verifying_key(verifying_key(&SIGNING_KEY))
+        .set_search_paths([BundleLocation::Absolute(bundle.to_path_buf())]);
+    BundleReader::open(options)
+}
+
+/// Rewrites the archive of `bundle` with the entries returned by `edit`, keeping the bundle header.
+fn rewrite_entries(bundle: &Path, edit: impl FnOnce(&mut Vec<(tar::Header, Vec<u8>)>)) {
+    let bytes = fs::read(bundle).unwrap();
+    let mut entries = Vec::new();
+    for entry in tar::Archive::new(&bytes[HEADER_LEN..]).entries().unwrap() {
+        let mut entry = entry.unwrap();
+        let header = entry.header().clone();
+        let mut data = Vec::new();
+        entry.read_to_end(&mut data).unwrap();
+        entries.push((header, data));
+    }
+    edit(&mut entries);
+
+    let mut builder = tar::Builder::new(bytes[..HEADER_LEN].to_vec());
+    for (mut header, data) in entries {
+        header.set_size(data.len() as u64);
+        header.set_cksum();
+        builder.append(&header, &data[..]).unwrap();
+    }
+    fs::write(bundle, builder.into_inner().unwrap()).unwrap();
+}
+
+fn entry_named<'a>(entries: &'a mut [(tar::Header, Vec<u8>)], name: &str) -> &'a mut (tar::Header, Vec<u8>) {
+    entries
+        .iter_mut()
+        .find(|(header, _)| header.path().unwrap() == Path::new(name))
+        .unwrap()
+}
+
+fn assert_rejected(bundle: &Path, message: &str) {
+    let err = open(bundle).err().expect("bundle was opened");
+    assert!(err.to_string().contains(message), "unexpected error: {:#}", err);
+}
+
+#[test]
+fn reads_bundle_signed_with_matching_key() {
+    let dir = TempDir::new("signed");
+    let (assets, bundle) = pack_signed(&dir, SIGNING_KEY);
+
+    let reader = open(&bundle).unwrap();
+    let mut paths: Vec<_> = reader.entries().into_iter().map(|entry| entry.path).collect();
+    paths.sort();
+    let original = read_tree(&assets);
+    assert_eq!(paths, original.keys().cloned().collect::<Vec<_>>());
+    for (path, data) in &original {
+        assert_eq!(&reader.read(path).unwrap(), data, "{}", path.display());
+    }
+}
+
+#[test]
+fn rejects_modified_entry() {
+    let dir = TempDir::new("signed_modified");
+    let (_, bundle) = pack_signed(&dir, SIGNING_KEY);
+    rewrite_entries(&bundle, |entries| entry_named(entries, "notes.txt").1[10] ^= 1);
+
+    let reader = open(&bundle).unwrap();
+    let err = reader
+        .read(Path::new("notes.txt"))
+        .expect_err("modified entry was loaded");
+    let err = err.downcast_ref::<EntryError>().expect("not an entry error");
+    assert_eq!(err.stage, EntryStage::Verify);
+    assert!(err.is_integrity_failure());
+}
+
+#[test]
+fn rejects_bundle_signed_with_another_key() {
+    let dir = TempDir::new("signed_other_key");
+    let (_, bundle) = pack_signed(&dir, [4; 32]);
+    assert_rejected(&bundle, "signature is invalid");
+}
+
+#[test]
+fn rejects_missing_manifest() {
+    let dir = TempDir::new("signed_no_manifest");
+    let (_, bundle) = pack_signed(&dir, SIGNING_KEY);
+    rewrite_entries(&bundle, |entries| {
+        entries.retain(|(header, _)| header.path().unwrap() != Path::new(MANIFEST))
+    });
+    assert_rejected(&bundle, "has no manifest");
+}
+
+#[test]
+fn rejects_truncated_manifest() {
+    let dir = TempDir::new("signed_truncated_manifest");
+    let (_, bundle) = pack_signed(&dir, SIGNING_KEY);
+    rewrite_entries(&bundle, |entries| {
+        let manifest = &mut entry_named(entries, MANIFEST).1;
+        manifest.truncate(manifest.len() - 1);
+    });
+    assert_rejected(&bundle, "signature is invalid");
+}
+
+#[test]
+fn rejects_unsigned_entry() {
+    let dir = TempDir::new("signed_extra_entry");
+    let (_, bundle) = pack_signed(&dir, SIGNING_KEY);
+    rewrite_entries(&bundle, |entries| {
+        let (mut header, data) = entry_named(entries, "notes.txt").clone();
+        header.set_path("extra.txt").unwrap();
+        entries.insert(0, (header, data));
+    });
+    assert_rejected(&bundle, "extra.txt is not signed");
+}
+
+#[test]
+fn rejects_duplicate_entry() {
+    let dir = TempDir::new("signed_duplicate_entry");
+    let (_, bundle) = pack_signed(&dir, SIGNING_KEY);
+    rewrite_entries(&bundle, |entries| {
+        let duplicate = entry_named(entries, "notes.txt").clone();
+        entries.insert(0, duplicate);
+    });
+    assert_rejected(&bundle, "notes.txt is not signed");
+}
@@ -0,0 +1,187 @@
+//! Modified bundles must be rejected, entries in the authenticated cipher mode and corrupt archives in every mode.
+
+#![cfg(feature = "bundler")]
+
+mod common;
+
+use std::{fs, path::Path};
+
+use bevy_asset_packer::{
+    asset_bundling_options::{AssetBundlingOptions, CipherMode, EncryptionKey},
+    bundle_location::BundleLocation,
+    bundle_reader::BundleReader,
+    error::EntryError,
+};
+use common::{pack, TempDir};
+
+/// Magic, format version, flags, cipher mode and cipher of a bundle without key salt.
+const HEADER_LEN: usize = 9;
+
+fn keys() -> [EncryptionKey; 3] {
+    [
+        EncryptionKey::Aes128([7; 16]),
+        EncryptionKey::Aes256([8; 32]),
+        EncryptionKey::ChaCha20([9; 32]),
+    ]
+}
+
+fn gcm_options(key: EncryptionKey) -> AssetBundlingOptions {
+    let mut options = AssetBundlingOptions::default();
+    options.with_cipher_mode(CipherMode::Gcm).set_encryption_key(key);
+    options
+}
+
+/// Packs `files` and returns the bundle path.
+fn pack_files(dir: &TempDir, options: &AssetBundlingOptions, files: &[(&str, &[u8])]) -> std::path::PathBuf {
+    let assets = dir.path().join("assets");
+    for (path, data) in files {
+        let path = assets.join(path);
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(path, data).unwrap();
+    }
+    let bundle = dir.path().join("assets.bin");
+    pack(options.clone(), &assets, &bundle).unwrap();
+    bundle
+}
+
+/// Byte range of the stored data of the entry named `name` in `bundle`.
+fn entry_range(bundle: &[u8], name: &str) -> std::ops::Range<usize> {
+    let mut archive = tar::Archive::new(&bundle[HEADER_LEN..]);
+    for entry in archive.entries().unwrap() {
+        let entry = entry.unwrap();
+        if entry.path().unwrap() == Path::new(name) {
+            let start = HEADER_LEN + entry.raw_file_position() as usize;
+            return start..start + entry.size() as usize;
+        }
+    }
+    panic!("bundle has no entry {}", name);
+}
+
+fn read(options: &AssetBundlingOptions, bundle: &Path, path: &str) -> anyhow::Result<Vec<u8>> {
+    let mut options = options.clone();
+    options.set_search_paths([BundleLocation::Absolute(bundle.to_path_buf())]);
+    BundleReader::open(options)?.read(Path::new(path))
+}
+
+fn assert_integrity_failure(result: anyhow::Result<Vec<u8>>) {
+    let err = result.expect_err("modified entry was loaded");
+    assert!(
+        err.downcast_ref::<EntryError>()
+            .is_some_and(EntryError::is_integrity_failure),
+        "unexpected error: {:#}",
+        err
+    );
+    assert_eq!(
+        format!("{:#}", err).matches("integrity check failed").count(),
+        1,
+        "{:#}",
+        err
+    );
+}
+
+#[test]
+fn rejects_modified_entry_body() {
+    for key in keys() {
+        let dir = TempDir::new("tamper_body");
+        let options = gcm_options(key);
+        let bundle = pack_files(&dir, &options, &[("notes.txt", b"first line\nsecond line\n")]);
+
+        let mut bytes = fs::read(&bundle).unwrap();
+        let range = entry_range(&bytes, "notes.txt");
+        bytes[range.start + range.len() / 2] ^= 1;
+        fs::write(&bundle, bytes).unwrap();
+
+        assert_integrity_failure(read(&options, &bundle, "notes.txt"));
+    }
+}
+
+#[test]
+fn rejects_swapped_entries() {
+    for key in keys() {
+        let dir = TempDir::new("tamper_swap");
+        let options = gcm_options(key);
+        let bundle = pack_files(
+            &dir,
+            &options,
+            &[("a.txt", b"contents of a"), ("b.txt", b"contents of b")],
+        );
+        assert_eq!(read(&options, &bundle, "a.txt").unwrap(), b"contents of a");
+
+        let mut bytes = fs::read(&bundle).unwrap();
+        let a = entry_range(&bytes, "a.txt");
+        let b = entry_range(&bytes, "b.txt");
+        assert_eq!(a.len(), b.len());
+        let a_data = bytes[a.clone()].to_vec();
+        bytes.copy_within(b.clone(), a.start);
+        bytes[b].copy_from_slice(&a_data);
+        fs::write(&bundle, bytes).unwrap();
+
+        assert_integrity_failure(read(&options, &bundle, "a.txt"));
+        assert_integrity_failure(read(&options, &bundle, "b.txt"));
+    }
+}
+
+#[cfg(feature = "deflate")]
+#[test]
+fn rejects_modified_compression_id() {
+    use bevy_asset_packer::compression::Compression;
+
+    for key in keys() {
+        let dir = TempDir::new("tamper_compression");
+        let mut options = gcm_options(key);
+        options.compression = Compression::Deflate(9);
+        let notes = "the quick brown fox jumps over the lazy dog\n".repeat(50);
+        let bundle = pack_files(&dir, &options, &[("numbers.txt", notes.as_bytes())]);
+
+        let mut bytes = fs::read(&bundle).unwrap();
+        let id = entry_range(&bytes, "numbers.txt").start;
+        assert_eq!(bytes[id], 1, "entry is not deflated");
+        bytes[id] = 0;
+        fs::write(&bundle, bytes).unwrap();
+
+        assert_integrity_failure(read(&options, &bundle, "numbers.txt"));
+    }
+}
+
+#[test]
+fn rejects_entry_size_past_end_of_bundle() {
+    let dir = TempDir::new("tamper_size");
+    let options = AssetBundlingOptions::default();
+    let bundle = pack_files(
+        &dir,
+        &options,
+        &[("a.txt", b"contents of a"), ("z.txt", b"contents of z")],
+    );
+
+    let mut bytes = fs::read(&bundle).unwrap();
+    let header_start = entry_range(&bytes, "z.txt").start - 512;
+    let mut header = tar::Header::new_old();
+    header
+        .as_mut_bytes()
+        .copy_from_slice(&bytes[header_start..header_start + 512]);
+    header.set_size(0o77777777777);
+    header.set_cksum();
+    bytes[header_start..header_start + 512].copy_from_slice(header.as_bytes());
+    fs::write(&bundle, bytes).unwrap();
+
+    let err = read(&options, &bundle, "z.txt").expect_err("entry past the end of the bundle was indexed");
+    assert!(err.to_string().contains("past the end"), "unexpected error: {:#}", err);
+}
+
+#[test]
+fn rejects_unknown_format_version() {
+    let dir = TempDir::new("tamper_version");
+    let options = AssetBundlingOptions::default();
+    let bundle = pack_files(&dir, &options, &[("a.txt", b"contents of a")]);
+
+    let mut bytes = fs::read(&bundle).unwrap();
+    bytes[4..6].copy_from_slice(&2u16.to_le_bytes());
+    fs::write(&bundle, bytes).unwrap();
+
+    let err = read(&options, &bundle, "a.txt").expect_err("bundle of an unknown version was read");
+    assert!(
+        err.to_string().contains("format version 2"),
+        "unexpected error: {:#}",
+        err
+    );
+}